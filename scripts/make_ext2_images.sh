@@ -0,0 +1,66 @@
+#!/bin/sh
+# Builds EXT2 test images with 1K, 2K and 4K blocks from the same files, then checks that
+# the tool reads every file back exactly and that deleting leaves the volume clean for e2fsck.
+#
+# usage: scripts/make_ext2_images.sh [OUTPUT_DIR]   (needs mke2fs and e2fsck)
+# The tool is built first, unless BIN points at an already built one.
+set -e
+
+out=${1:-test_images}
+if [ -z "$BIN" ]; then
+    cargo build --quiet
+fi
+bin=${BIN:-target/debug/aos_fat16_ext2_2021}
+if [ ! -x "$bin" ]; then
+    echo "$bin is not an executable, build the tool or point BIN at it" >&2
+    exit 1
+fi
+tree="$out/tree"
+
+rm -rf "$tree"
+mkdir -p "$tree/docs/a" "$tree/manydir"
+
+printf 'hello world\n' > "$tree/hello.txt"
+printf 'log\n' > "$tree/app.log"
+printf 'core\n' > "$tree/core.123"
+printf 'docs readme\n' > "$tree/docs/readme.txt"
+printf 'docs/a readme\n' > "$tree/docs/a/readme.txt"
+# enough entries for the directory to span several blocks even with 4K blocks
+i=0
+while [ $i -lt 300 ]; do
+    printf '%s\n' $i > "$tree/manydir/file_with_long_name_$(printf '%03d' $i).txt"
+    i=$((i + 1))
+done
+printf 'deep needle\n' > "$tree/manydir/needle.txt"
+# past the 12 direct blocks and the indirect block, so it needs the double indirect block
+head -c 3000000 /dev/urandom > "$tree/big.bin"
+# mostly holes, with data far enough to need the triple indirect block with 1K blocks
+truncate -s 0 "$tree/sparse.bin"
+printf 'start' | dd of="$tree/sparse.bin" conv=notrunc 2>/dev/null
+printf 'end' | dd of="$tree/sparse.bin" bs=1024 seek=71680 conv=notrunc 2>/dev/null
+
+for block_size in 1024 2048 4096; do
+    image="$out/ext2_$block_size.img"
+    rm -f "$image"
+    mke2fs -q -F -t ext2 -b $block_size -d "$tree" "$image" 16M
+
+    for file in $(cd "$tree" && find . -type f | sed 's|^\.||'); do
+        if ! "$bin" /cat "$image" "$file" | cmp -s - "$tree$file"; then
+            echo "$image: $file does not match" >&2
+            exit 1
+        fi
+    done
+
+    cp "$image" "$out/deleted.img"
+    "$bin" /delete "$out/deleted.img" /big.bin > /dev/null
+    "$bin" /delete "$out/deleted.img" /sparse.bin > /dev/null
+    "$bin" /delete "$out/deleted.img" 'file_with_long_name_1*' --all > /dev/null
+    if ! e2fsck -fn "$out/deleted.img" > "$out/e2fsck.txt" 2>&1; then
+        echo "$image: e2fsck finds errors after deleting, see $out/deleted.img" >&2
+        cat "$out/e2fsck.txt" >&2
+        exit 1
+    fi
+    rm "$out/deleted.img" "$out/e2fsck.txt"
+
+    echo "$image: ok"
+done
@@ -1,42 +1,63 @@
+use crate::detect::*;
+use crate::device::*;
+use crate::error::*;
 use crate::ext2::*;
-use crate::fat16::*;
+use crate::fat::*;
 use crate::filesystem::*;
-use crate::utilities::*;
-use byteorder::{ByteOrder, LittleEndian};
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+use std::io::ErrorKind;
 
-//check exists
-//check type (check at least size of the nearest offset)
-// Perhaps change the Box implementation?
-pub fn check_file(myfile: &str) -> Box<dyn Filesystem> {
-    //check exists and if can be open it
-    let mut opened_file = match File::open(&myfile) {
-        Err(why) => panic!("couldn't open {}: {}", myfile, why),
-        Ok(opened_file) => opened_file,
-    };
+/// Detects the filesystem of the image at `myfile` and loads its information.
+pub fn check_file(myfile: &str) -> Result<Box<dyn Filesystem>> {
+    return check_device(open_image(myfile)?);
+}
+
+/// Like `check_file`, for what only EXT2 volumes have, such as inodes and the journal.
+pub fn check_ext2_file(myfile: &str) -> Result<Ext2<'static>> {
+    let opened_file = open_image(myfile)?;
+    let detection = detect(&opened_file)?;
+    if detection.confidence < MIN_CONFIDENCE {
+        return Err(Error::NotAFilesystem);
+    }
+
+    match detection.filesystem_type {
+        FilesystemType::Ext2 => return Ext2::new(opened_file),
+        filesystem_type => {
+            return Err(Error::Unsupported(format!(
+                "the volume is {}, not EXT2",
+                filesystem_type
+            )))
+        }
+    }
+}
 
-    if opened_file.metadata().unwrap().len() < 56 + 1024 {
-        panic!("File system is neither Fat16 nor Ext2");
+/// Detects the filesystem on `device`, which the returned `Filesystem` keeps.
+pub fn check_device<'a>(device: impl BlockDevice + 'a) -> Result<Box<dyn Filesystem + 'a>> {
+    let detection = detect(&device)?;
+    if detection.confidence < MIN_CONFIDENCE {
+        return Err(Error::NotAFilesystem);
     }
-    //Create a buffer of 2 bytes for reading to see if it is a Fat16 or 32
-    let fat_buf: &mut [u8] = &mut [0; 2];
-    //Create a buffer of 2 bytes for reading to see if it is a Fat16 or 32
-    let ext2_buf: &mut [u8] = &mut [0; 2];
 
-    //Start at 22 since this is BPB_FATSz16, if not 0, it is a FAT16 volume
-    utilities::seek_read(&mut opened_file, 22, fat_buf).unwrap();
-    //For knowing if it is ext2, we check 2 bytes starting at offset 56 + 1024 (cus superblock)
-    utilities::seek_read(&mut opened_file, 56 + 1024, ext2_buf).unwrap();
+    match detection.filesystem_type {
+        //the FAT module tells FAT12, FAT16 and FAT32 apart by the cluster count, as detection did
+        FilesystemType::Fat12 | FilesystemType::Fat16 | FilesystemType::Fat32 => {
+            return Ok(Box::new(Fat::new(device)?))
+        }
+        FilesystemType::Ext2 => return Ok(Box::new(Ext2::new(device)?)),
+    }
+}
 
-    //check what file it is
-    // Check if FS is ext2 or FAT16 or neither
-    if LittleEndian::read_u16(fat_buf) == 16 {
-        //println!("EXT2 FS!");
-        return Box::new(Fat16::default());
-    } else if LittleEndian::read_u16(ext2_buf) == 61267 {
-        //println!("FAT16 FS!");
-        return Box::new(Ext2::default());
-    } else {
-        panic!("File system is neither Fat16 nor Ext2");
+fn open_image(myfile: &str) -> Result<File> {
+    //check exists and if can be open it, read only images can still be inspected
+    match OpenOptions::new().read(true).write(true).open(myfile) {
+        Err(e)
+            if matches!(
+                e.kind(),
+                ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem
+            ) =>
+        {
+            return Ok(File::open(myfile)?)
+        }
+        result => return Ok(result?),
     }
 }
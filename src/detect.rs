@@ -0,0 +1,414 @@
+use crate::device::*;
+use crate::error::*;
+use crate::ext2::*;
+use crate::fat::*;
+use byteorder::{ByteOrder, LittleEndian};
+use std::fmt;
+
+//below this the volume is not considered to be of any type
+pub const MIN_CONFIDENCE: u8 = 50;
+
+/// The kinds of volume `detect` knows about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilesystemType {
+    Fat12,
+    Fat16,
+    Fat32,
+    Ext2,
+}
+
+/// One check made while detecting a volume, and whether it passed.
+#[derive(Debug, Clone)]
+pub struct Evidence {
+    pub description: String,
+    pub passed: bool,
+    weight: u32,
+}
+
+/// The most likely type of a volume, how sure `detect` is about it and why.
+#[derive(Debug, Clone)]
+pub struct Detection {
+    pub filesystem_type: FilesystemType,
+    /// From 0 to 100, the share of the checks for this type that passed. A failed
+    /// check that the type can't do without makes it 0.
+    pub confidence: u8,
+    pub evidence: Vec<Evidence>,
+}
+
+impl fmt::Display for FilesystemType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilesystemType::Fat12 => write!(f, "FAT12"),
+            FilesystemType::Fat16 => write!(f, "FAT16"),
+            FilesystemType::Fat32 => write!(f, "FAT32"),
+            FilesystemType::Ext2 => write!(f, "EXT2"),
+        }
+    }
+}
+
+impl Detection {
+    fn new(filesystem_type: FilesystemType) -> Detection {
+        return Detection {
+            filesystem_type,
+            confidence: 0,
+            evidence: Vec::new(),
+        };
+    }
+
+    fn check(&mut self, weight: u32, passed: bool, description: String) {
+        self.evidence.push(Evidence {
+            description,
+            passed,
+            weight,
+        });
+    }
+
+    //the type is ruled out, the evidence says why
+    fn reject(mut self, description: String) -> Detection {
+        self.check(0, false, description);
+        self.confidence = 0;
+        return self;
+    }
+
+    fn score(mut self) -> Detection {
+        let total: u32 = self.evidence.iter().map(|e| e.weight).sum();
+        let passed: u32 = self
+            .evidence
+            .iter()
+            .filter(|e| e.passed)
+            .map(|e| e.weight)
+            .sum();
+        self.confidence = (passed * 100 / total.max(1)) as u8;
+        return self;
+    }
+}
+
+/// Looks at the boot sector and the superblock of `device` and returns the type it is
+/// most likely to be. The caller decides whether the confidence is enough.
+pub fn detect(device: &dyn BlockDevice) -> Result<Detection> {
+    let fat = detect_fat(device)?;
+    let ext2 = detect_ext2(device)?;
+
+    if ext2.confidence > fat.confidence {
+        return Ok(ext2);
+    }
+    return Ok(fat);
+}
+
+fn detect_fat(device: &dyn BlockDevice) -> Result<Detection> {
+    //the type is only known once the clusters are counted
+    let mut detection = Detection::new(FilesystemType::Fat16);
+
+    let device_size = device.size()?;
+    if device_size < 512 {
+        return Ok(detection.reject(String::from("the volume is smaller than a boot sector")));
+    }
+    let boot_sector: &mut [u8] = &mut [0; 512];
+    device.read_at(0, boot_sector)?;
+
+    // ------------------------ BPB ------------------------
+    let read_u8 = |offset: u64| boot_sector[offset as usize] as u64;
+    let read_u16 = |offset: u64| LittleEndian::read_u16(&boot_sector[offset as usize..]) as u64;
+    let read_u32 = |offset: u64| LittleEndian::read_u32(&boot_sector[offset as usize..]) as u64;
+    let sector_size = read_u16(BPB_BytsPerSec);
+    let sectors_per_cluster = read_u8(BPB_SecPerClus);
+    let reserved_sectors = read_u16(BPB_RsvdSecCnt);
+    let num_fats = read_u8(BPB_NumFATs);
+    let root_entries = read_u16(BPB_RootEntCnt);
+    let media = read_u8(BPB_Media);
+    let total_sectors = match read_u16(BPB_TotSec16) {
+        0 => read_u32(BPB_TotSec32),
+        total_sectors => total_sectors,
+    };
+    //FAT32 leaves the 16 bit FAT size at 0 and has a 32 bit one instead
+    let fat16_size = read_u16(BPB_FATSz16);
+    let sectors_per_fat = match fat16_size {
+        0 => read_u32(BPB_FATSz32),
+        sectors_per_fat => sectors_per_fat,
+    };
+
+    if ![512, 1024, 2048, 4096].contains(&sector_size) {
+        return Ok(detection.reject(format!("{} is not a valid sector size", sector_size)));
+    }
+    if !sectors_per_cluster.is_power_of_two() || sectors_per_cluster > 128 {
+        return Ok(detection.reject(format!(
+            "{} is not a valid number of sectors per cluster",
+            sectors_per_cluster
+        )));
+    }
+    if reserved_sectors == 0 || num_fats == 0 || sectors_per_fat == 0 {
+        return Ok(detection.reject(String::from(
+            "the BPB has no reserved sectors, no FATs or empty FATs",
+        )));
+    }
+
+    let root_dir_sectors = (root_entries * 32).div_ceil(sector_size);
+    let metadata_sectors = reserved_sectors + num_fats * sectors_per_fat + root_dir_sectors;
+    if metadata_sectors >= total_sectors {
+        return Ok(detection.reject(format!(
+            "the volume has {} sectors, but its FATs and root directory end at sector {}",
+            total_sectors, metadata_sectors
+        )));
+    }
+    detection.check(
+        30,
+        true,
+        format!(
+            "the BPB is sane: {} byte sectors, {} sectors per cluster, {} FATs of {} sectors",
+            sector_size, sectors_per_cluster, num_fats, sectors_per_fat
+        ),
+    );
+
+    // ------------------------ FAT TYPE ------------------------
+    let cluster_count = (total_sectors - metadata_sectors) / sectors_per_cluster;
+    let (filesystem_type, entry_bits, fs_type_offset) =
+        match FatType::from_cluster_count(cluster_count) {
+            FatType::Fat12 => (FilesystemType::Fat12, 12, BS_FilSysType),
+            FatType::Fat16 => (FilesystemType::Fat16, 16, BS_FilSysType),
+            FatType::Fat32 => (FilesystemType::Fat32, 32, BS_FilSysType32),
+        };
+    detection.filesystem_type = filesystem_type;
+    detection.check(
+        0,
+        true,
+        format!("{} clusters make it {}", cluster_count, filesystem_type),
+    );
+
+    // ------------------------ CONSISTENCY ------------------------
+    detection.check(
+        20,
+        LittleEndian::read_u16(&boot_sector[510..512]) == 0xAA55,
+        String::from("boot signature 0x55AA at offset 510"),
+    );
+    detection.check(
+        10,
+        boot_sector[0] == 0xE9 || (boot_sector[0] == 0xEB && boot_sector[2] == 0x90),
+        String::from("jump instruction at the start of the boot sector"),
+    );
+    detection.check(
+        10,
+        media == 0xF0 || media >= 0xF8,
+        format!("media descriptor 0x{:02X}", media),
+    );
+
+    let fs_type_offset = fs_type_offset as usize;
+    let fs_type = String::from_utf8_lossy(&boot_sector[fs_type_offset..fs_type_offset + 8])
+        .trim_end()
+        .to_string();
+    //the label is only informative, formatters often write plain "FAT"
+    detection.check(
+        20,
+        fs_type == filesystem_type.to_string() || fs_type == "FAT",
+        format!("BS_FilSysType is {:?}", fs_type),
+    );
+
+    if filesystem_type == FilesystemType::Fat32 {
+        detection.check(
+            10,
+            root_entries == 0 && fat16_size == 0,
+            String::from("no fixed root directory and no 16 bit FAT size, as FAT32 requires"),
+        );
+    } else {
+        detection.check(
+            10,
+            root_entries > 0 && (root_entries * 32).is_multiple_of(sector_size),
+            format!(
+                "a root directory of {} entries filling whole sectors",
+                root_entries
+            ),
+        );
+    }
+
+    detection.check(
+        10,
+        sectors_per_fat * sector_size * 8 >= (cluster_count + 2) * entry_bits,
+        format!("each FAT can hold the {} clusters", cluster_count),
+    );
+    detection.check(
+        10,
+        total_sectors * sector_size <= device_size,
+        format!(
+            "the {} sectors of the volume fit in the image",
+            total_sectors
+        ),
+    );
+
+    return Ok(detection.score());
+}
+
+fn detect_ext2(device: &dyn BlockDevice) -> Result<Detection> {
+    let mut detection = Detection::new(FilesystemType::Ext2);
+
+    let device_size = device.size()?;
+    if device_size < 2048 {
+        return Ok(detection.reject(String::from(
+            "the volume is too small to have a superblock at offset 1024",
+        )));
+    }
+    //the superblock field offsets count from the start of the volume, the superblock is at 1024
+    let superblock: &mut [u8] = &mut [0; 2048];
+    device.read_at(0, superblock)?;
+    let read_u16 = |offset: u64| LittleEndian::read_u16(&superblock[offset as usize..]);
+    let read_u32 = |offset: u64| LittleEndian::read_u32(&superblock[offset as usize..]);
+
+    if read_u16(s_magic) != 0xEF53 {
+        return Ok(detection.reject(String::from("no superblock magic 0xEF53 at offset 1080")));
+    }
+    detection.check(
+        30,
+        true,
+        String::from("superblock magic 0xEF53 at offset 1080"),
+    );
+
+    let log_block_size = read_u32(s_log_block_size);
+    if log_block_size > 6 {
+        return Ok(detection.reject(format!("{} is not a valid block size", log_block_size)));
+    }
+    let block_size = 1024u64 << log_block_size;
+
+    let num_inodes = read_u32(s_inodes_count) as u64;
+    let num_blocks = read_u32(s_blocks_count) as u64;
+    let free_blocks = read_u32(s_free_blocks_count) as u64;
+    let free_inodes = read_u32(s_free_inodes_count) as u64;
+    let first_data_block = read_u32(s_first_data_block) as u64;
+    let blocks_per_group = read_u32(s_blocks_per_group) as u64;
+    let inodes_per_group = read_u32(s_inodes_per_group) as u64;
+    let state = read_u16(s_state);
+    let rev_level = read_u32(s_rev_level);
+
+    //a bitmap is one block, so a group can't have more blocks or inodes than it has bits
+    detection.check(
+        10,
+        (1..=block_size * 8).contains(&blocks_per_group),
+        format!(
+            "{} blocks per group with {} byte blocks",
+            blocks_per_group, block_size
+        ),
+    );
+    detection.check(
+        10,
+        (1..=block_size * 8).contains(&inodes_per_group),
+        format!(
+            "{} inodes per group with {} byte blocks",
+            inodes_per_group, block_size
+        ),
+    );
+    //the superblock is in block 1 with 1K blocks and in block 0 otherwise
+    detection.check(
+        10,
+        first_data_block == (block_size == 1024) as u64,
+        format!("first data block {}", first_data_block),
+    );
+
+    let num_groups = num_blocks
+        .saturating_sub(first_data_block)
+        .div_ceil(blocks_per_group.max(1));
+    detection.check(
+        10,
+        num_inodes > 0 && num_inodes <= num_groups * inodes_per_group,
+        format!("{} groups can hold the {} inodes", num_groups, num_inodes),
+    );
+    detection.check(
+        10,
+        free_blocks <= num_blocks && free_inodes <= num_inodes,
+        String::from("no more free blocks or inodes than there are"),
+    );
+    detection.check(
+        10,
+        state == 1 || state == 2,
+        format!("filesystem state {}", state),
+    );
+    detection.check(10, rev_level <= 1, format!("revision {}", rev_level));
+    detection.check(
+        10,
+        num_blocks * block_size <= device_size,
+        format!("the {} blocks of the volume fit in the image", num_blocks),
+    );
+
+    return Ok(detection.score());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a boot sector as a formatter writes it, for a volume of `total_sectors` sectors of 512
+    //bytes with one sector per cluster and 2 FATs
+    fn fat_image(total_sectors: u16, fat_size: u16, root_entries: u16, fs_type: &[u8]) -> Vec<u8> {
+        let mut image = vec![0; total_sectors as usize * 512];
+        image[..3].copy_from_slice(&[0xEB, 0x3C, 0x90]);
+        LittleEndian::write_u16(&mut image[BPB_BytsPerSec as usize..], 512);
+        image[BPB_SecPerClus as usize] = 1;
+        LittleEndian::write_u16(&mut image[BPB_RsvdSecCnt as usize..], 1);
+        image[BPB_NumFATs as usize] = 2;
+        LittleEndian::write_u16(&mut image[BPB_RootEntCnt as usize..], root_entries);
+        LittleEndian::write_u16(&mut image[BPB_TotSec16 as usize..], total_sectors);
+        image[BPB_Media as usize] = 0xF8;
+        LittleEndian::write_u16(&mut image[BPB_FATSz16 as usize..], fat_size);
+        let fs_type_offset = BS_FilSysType as usize;
+        image[fs_type_offset..fs_type_offset + 8].copy_from_slice(fs_type);
+        image[510..512].copy_from_slice(&[0x55, 0xAA]);
+        return image;
+    }
+
+    //a clean single group volume of 128 blocks of 1024 bytes
+    fn ext2_image() -> Vec<u8> {
+        let mut image = vec![0; 128 * 1024];
+        LittleEndian::write_u32(&mut image[s_inodes_count as usize..], 16);
+        LittleEndian::write_u32(&mut image[s_blocks_count as usize..], 128);
+        LittleEndian::write_u32(&mut image[s_free_blocks_count as usize..], 100);
+        LittleEndian::write_u32(&mut image[s_free_inodes_count as usize..], 5);
+        LittleEndian::write_u32(&mut image[s_first_data_block as usize..], 1);
+        LittleEndian::write_u32(&mut image[s_blocks_per_group as usize..], 8192);
+        LittleEndian::write_u32(&mut image[s_inodes_per_group as usize..], 16);
+        LittleEndian::write_u16(&mut image[s_magic as usize..], 0xEF53);
+        LittleEndian::write_u16(&mut image[s_state as usize..], 1);
+        LittleEndian::write_u32(&mut image[s_rev_level as usize..], 1);
+        return image;
+    }
+
+    #[test]
+    fn fat_types_come_from_the_cluster_count() {
+        let detection = detect(&fat_image(64, 1, 16, b"FAT12   ")).unwrap();
+        assert_eq!(detection.filesystem_type, FilesystemType::Fat12);
+        assert_eq!(detection.confidence, 100);
+
+        let detection = detect(&fat_image(4267, 17, 512, b"FAT16   ")).unwrap();
+        assert_eq!(detection.filesystem_type, FilesystemType::Fat16);
+        assert_eq!(detection.confidence, 100);
+    }
+
+    #[test]
+    fn failed_checks_lower_the_confidence() {
+        //the label says FAT16 but 60 clusters make it FAT12, and the signature is missing
+        let mut image = fat_image(64, 1, 16, b"FAT16   ");
+        image[510..512].fill(0);
+        let detection = detect(&image).unwrap();
+        assert_eq!(detection.filesystem_type, FilesystemType::Fat12);
+        assert_eq!(detection.confidence, 66);
+        assert!(detection.confidence >= MIN_CONFIDENCE);
+        let failed = detection.evidence.iter().filter(|e| !e.passed).count();
+        assert_eq!(failed, 2);
+    }
+
+    #[test]
+    fn ext2_superblocks_are_detected() {
+        let detection = detect(&ext2_image()).unwrap();
+        assert_eq!(detection.filesystem_type, FilesystemType::Ext2);
+        assert_eq!(detection.confidence, 100);
+    }
+
+    #[test]
+    fn impossible_volumes_are_rejected() {
+        //the FATs and the root directory don't fit in the 40 sectors of the volume
+        let detection = detect(&fat_image(40, 17, 512, b"FAT16   ")).unwrap();
+        assert_eq!(detection.confidence, 0);
+
+        let mut image = ext2_image();
+        LittleEndian::write_u32(&mut image[s_log_block_size as usize..], 20);
+        assert_eq!(detect(&image).unwrap().confidence, 0);
+
+        assert_eq!(detect(&vec![0; 4096]).unwrap().confidence, 0);
+        assert_eq!(detect(&vec![0xFF; 100]).unwrap().confidence, 0);
+    }
+}
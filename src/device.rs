@@ -0,0 +1,114 @@
+use crate::utilities;
+use std::fs::File;
+use std::io;
+
+/// Where the bytes of a volume image come from.
+///
+/// Implemented for image files, in-memory images (`Vec<u8>`) and read-only byte slices,
+/// so a volume embedded in some other data can be parsed without a temporary file.
+pub trait BlockDevice {
+    /// Fills `buf` with the bytes starting at `offset`.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()>;
+    /// Writes all of `buf` starting at `offset`.
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()>;
+    /// The size of the device in bytes.
+    fn size(&self) -> io::Result<u64>;
+}
+
+impl BlockDevice for File {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        return utilities::seek_read(self, offset, buf);
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()> {
+        return utilities::seek_write(self, offset.into(), buf);
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        return Ok(self.metadata()?.len());
+    }
+}
+
+impl BlockDevice for Vec<u8> {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        return self.as_slice().read_at(offset, buf);
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> io::Result<()> {
+        //an image has a fixed size, writing past its end is a bug and not a reason to grow it
+        let range = get_range(self.len(), offset, buf.len())?;
+        self[range].copy_from_slice(buf);
+        return Ok(());
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        return Ok(self.len() as u64);
+    }
+}
+
+impl BlockDevice for &[u8] {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let range = get_range(self.len(), offset, buf.len())?;
+        buf.copy_from_slice(&self[range]);
+        return Ok(());
+    }
+
+    fn write_at(&mut self, _offset: u64, _buf: &[u8]) -> io::Result<()> {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the image is read only",
+        ));
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        return Ok(self.len() as u64);
+    }
+}
+
+fn get_range(device_size: usize, offset: u64, len: usize) -> io::Result<std::ops::Range<usize>> {
+    let start = offset as usize;
+    match start.checked_add(len) {
+        Some(end) if offset <= usize::MAX as u64 && end <= device_size => return Ok(start..end),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "read or write past the end of the image",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_memory_images_have_a_fixed_size() {
+        let mut image: Vec<u8> = vec![0; 16];
+        image.write_at(12, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(image.len(), 16);
+
+        let error = image.write_at(14, &[5, 6, 7]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(image.len(), 16);
+        assert_eq!(image[12..], [1, 2, 3, 4]);
+
+        let buf = &mut [0; 4];
+        image.read_at(12, buf).unwrap();
+        assert_eq!(buf, &[1, 2, 3, 4]);
+        assert!(image.read_at(13, buf).is_err());
+        assert!(image.read_at(u64::MAX, buf).is_err());
+    }
+
+    #[test]
+    fn slices_are_read_only() {
+        let mut image: &[u8] = &[1, 2, 3, 4];
+        let buf = &mut [0; 2];
+        image.read_at(2, buf).unwrap();
+        assert_eq!(buf, &[3, 4]);
+
+        let error = image.write_at(0, &[9]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(image, [1, 2, 3, 4]);
+    }
+}
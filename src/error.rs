@@ -0,0 +1,79 @@
+use std::fmt;
+use std::io;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can go wrong while reading or writing a volume.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    NotAFilesystem,
+    CorruptStructure(String),
+    NotFound(String),
+    InvalidName(String),
+    Unsupported(String),
+    NothingSelected(String),
+    InvalidPattern(String),
+    InvalidDate(String),
+}
+
+impl Error {
+    //every kind of error gets its own exit code, so scripts can tell them apart
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io(_) => 2,
+            Error::NotAFilesystem => 3,
+            Error::CorruptStructure(_) => 4,
+            Error::NotFound(_) => 5,
+            Error::InvalidName(_) => 6,
+            Error::Unsupported(_) => 7,
+            Error::NothingSelected(_) => 8,
+            Error::InvalidPattern(_) => 9,
+            Error::InvalidDate(_) => 10,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::NotAFilesystem => write!(f, "File system is neither FAT nor Ext2"),
+            Error::CorruptStructure(what) => write!(f, "Corrupt filesystem: {}", what),
+            Error::NotFound(name) => write!(f, "could not find the file {} :(", name),
+            Error::InvalidName(name) => write!(f, "Invalid file name: {:?}", name),
+            Error::Unsupported(what) => write!(f, "Not supported: {}", what),
+            Error::NothingSelected(action) => write!(f, "No file selected, nothing to {}.", action),
+            Error::InvalidPattern(what) => write!(f, "Invalid pattern {}", what),
+            Error::InvalidDate(date) => write!(
+                f,
+                "Invalid date {}, use YYYY-MM-DD or \"YYYY-MM-DD HH:MM:SS\"",
+                date
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        match e {
+            Error::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+        }
+    }
+}
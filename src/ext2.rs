@@ -1,29 +1,118 @@
 #![allow(non_upper_case_globals)]
+use crate::device::*;
+use crate::error::*;
 use crate::filesystem::*;
-use crate::utilities::*;
-use byteorder::{ByteOrder, LittleEndian};
-use std::fs::File;
-use std::fs::OpenOptions;
-use std::str;
-
-const s_wtime: u64 = 1024 + 48;
-const s_lastcheck: u64 = 1024 + 64;
-const s_mtime: u64 = 1024 + 44;
-const s_volume_name: u64 = 1024 + 120;
-const s_inodes_count: u64 = 1024 + 0;
-const s_inodes_per_group: u64 = 1024 + 40;
-const s_first_ino: u64 = 1024 + 84;
-const s_free_inodes_count: u64 = 1024 + 16;
-const s_inode_size: u64 = 1024 + 88;
-const s_free_blocks_count: u64 = 1024 + 12;
-const s_log_block_size: u64 = 1024 + 24;
-const s_r_blocks_count: u64 = 1024 + 8;
-const s_blocks_count: u64 = 1024 + 4;
-const s_first_data_block: u64 = 1024 + 20;
-const s_blocks_per_group: u64 = 1024 + 32;
-const s_frags_per_group: u64 = 1024 + 36;
-
-pub struct Ext2 {
+use crate::utilities;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) const s_wtime: u64 = 1024 + 48;
+pub(crate) const s_lastcheck: u64 = 1024 + 64;
+pub(crate) const s_mtime: u64 = 1024 + 44;
+pub(crate) const s_volume_name: u64 = 1024 + 120;
+pub(crate) const s_inodes_count: u64 = 1024;
+pub(crate) const s_inodes_per_group: u64 = 1024 + 40;
+pub(crate) const s_first_ino: u64 = 1024 + 84;
+pub(crate) const s_free_inodes_count: u64 = 1024 + 16;
+pub(crate) const s_inode_size: u64 = 1024 + 88;
+pub(crate) const s_free_blocks_count: u64 = 1024 + 12;
+pub(crate) const s_log_block_size: u64 = 1024 + 24;
+pub(crate) const s_r_blocks_count: u64 = 1024 + 8;
+pub(crate) const s_blocks_count: u64 = 1024 + 4;
+pub(crate) const s_first_data_block: u64 = 1024 + 20;
+pub(crate) const s_blocks_per_group: u64 = 1024 + 32;
+pub(crate) const s_frags_per_group: u64 = 1024 + 36;
+pub(crate) const s_magic: u64 = 1024 + 56;
+pub(crate) const s_state: u64 = 1024 + 58;
+pub(crate) const s_rev_level: u64 = 1024 + 76;
+pub(crate) const s_feature_compat: u64 = 1024 + 92;
+pub(crate) const s_feature_incompat: u64 = 1024 + 96;
+pub(crate) const s_feature_ro_compat: u64 = 1024 + 100;
+pub(crate) const s_journal_inum: u64 = 1024 + 224;
+
+//names as printed by dumpe2fs, in bit order
+const COMPAT_FEATURES: [(u32, &str); 8] = [
+    (0x0001, "dir_prealloc"),
+    (0x0002, "imagic_inodes"),
+    (0x0004, "has_journal"),
+    (0x0008, "ext_attr"),
+    (0x0010, "resize_inode"),
+    (0x0020, "dir_index"),
+    (0x0080, "sparse_super2"),
+    (0x0200, "stable_inodes"),
+];
+const INCOMPAT_FEATURES: [(u32, &str); 16] = [
+    (0x0001, "compression"),
+    (0x0002, "filetype"),
+    (0x0004, "needs_recovery"),
+    (0x0008, "journal_dev"),
+    (0x0010, "meta_bg"),
+    (0x0040, "extent"),
+    (0x0080, "64bit"),
+    (0x0100, "mmp"),
+    (0x0200, "flex_bg"),
+    (0x0400, "ea_inode"),
+    (0x1000, "dirdata"),
+    (0x2000, "metadata_csum_seed"),
+    (0x4000, "large_dir"),
+    (0x8000, "inline_data"),
+    (0x10000, "encrypt"),
+    (0x20000, "casefold"),
+];
+const RO_COMPAT_FEATURES: [(u32, &str); 13] = [
+    (0x0001, "sparse_super"),
+    (0x0002, "large_file"),
+    (0x0004, "btree_dir"),
+    (0x0008, "huge_file"),
+    (0x0010, "uninit_bg"),
+    (0x0020, "dir_nlink"),
+    (0x0040, "extra_isize"),
+    (0x0100, "quota"),
+    (0x0200, "bigalloc"),
+    (0x0400, "metadata_csum"),
+    (0x1000, "read-only"),
+    (0x2000, "project"),
+    (0x8000, "verity"),
+];
+
+const COMPAT_HAS_JOURNAL: u32 = 0x0004;
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+//set while the journal has transactions that are not in the filesystem yet
+const INCOMPAT_RECOVER: u32 = 0x0004;
+//i_flags bit of an inode whose i_block holds an extent tree
+const EXTENTS_FL: u32 = 0x0008_0000;
+//h_magic of an extended attribute block
+const XATTR_MAGIC: u32 = 0xEA02_0000;
+//the only features remove knows how to keep consistent
+const WRITE_INCOMPAT: u32 = INCOMPAT_FILETYPE;
+const WRITE_RO_COMPAT: u32 = 0x0001 | 0x0002;
+
+//the journal is big endian, unlike the rest of the filesystem
+const JBD2_MAGIC: u32 = 0xC03B_3998;
+const JBD2_DESCRIPTOR_BLOCK: u32 = 1;
+const JBD2_COMMIT_BLOCK: u32 = 2;
+const JBD2_SUPERBLOCK_V1: u32 = 3;
+const JBD2_SUPERBLOCK_V2: u32 = 4;
+const JBD2_REVOKE_BLOCK: u32 = 5;
+const JBD2_FLAG_ESCAPE: u16 = 0x1;
+const JBD2_FLAG_SAME_UUID: u16 = 0x2;
+const JBD2_FLAG_LAST_TAG: u16 = 0x8;
+const JOURNAL_COMPAT_FEATURES: [(u32, &str); 1] = [(0x1, "journal_checksum")];
+const JOURNAL_INCOMPAT_FEATURES: [(u32, &str); 6] = [
+    (0x01, "journal_incompat_revoke"),
+    (0x02, "journal_64bit"),
+    (0x04, "journal_async_commit"),
+    (0x08, "journal_checksum_v2"),
+    (0x10, "journal_checksum_v3"),
+    (0x20, "journal_fast_commit"),
+];
+//revoke records and asynchronous commits don't change the layout of the journal blocks
+const REPLAY_JOURNAL_INCOMPAT: u32 = 0x01 | 0x04;
+
+pub struct Ext2<'a> {
+    pub device: Box<dyn BlockDevice + 'a>,
     pub volume_name: [u8; 16],
     pub last_mounted: [u8; 4],
     pub last_check: [u8; 4],
@@ -41,31 +130,112 @@ pub struct Ext2 {
     pub first_data_block: u32,
     pub blocks_per_group: u32,
     pub frags_per_group: u32,
+    pub rev_level: u32,
+    pub feature_compat: u32,
+    pub feature_incompat: u32,
+    pub feature_ro_compat: u32,
+    //0 without a journal, or when it is on another device
+    pub journal_inode: u32,
+    pub group_descs: Vec<GroupDesc>,
 }
 
+/// One entry of the block group descriptor table.
+#[derive(Debug, Clone, Default)]
+pub struct GroupDesc {
+    pub block_bitmap: u32,
+    pub inode_bitmap: u32,
+    pub inode_table: u32,
+    pub free_blocks_count: u16,
+    pub free_inodes_count: u16,
+    pub used_dirs_count: u16,
+}
+
+#[derive(Default, Clone)]
 struct DirEntry {
     pub inode: [u8; 4],
     pub rec_len: [u8; 2],
     pub name_len: [u8; 1],
     pub file_type: [u8; 1],
     pub name: Vec<u8>,
+    pub offset: u64,
+    pub prev_offset: Option<u64>,
 }
 
-impl Default for DirEntry {
-    fn default() -> DirEntry {
-        DirEntry {
-            inode: [0; 4],
-            rec_len: [0; 2],
-            name_len: [0; 1],
-            file_type: [0; 1],
-            name: Vec::new(),
-        }
-    }
+/// Everything stored in an inode, as shown by `/stat`.
+#[derive(Debug, Clone)]
+pub struct Inode {
+    pub number: u32,
+    pub mode: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub atime: u32,
+    pub ctime: u32,
+    pub mtime: u32,
+    pub dtime: u32,
+    pub links_count: u16,
+    //in 512 byte sectors, whatever the block size
+    pub blocks: u32,
+    pub flags: u32,
+    pub version: u32,
+    pub generation: u32,
+    pub file_acl: u32,
+    pub faddr: u32,
+    pub frag: u8,
+    pub fsize: u8,
+    //12 direct blocks, then the indirect, double indirect and triple indirect block
+    pub block: [u32; 15],
+    //the target of a fast symlink is stored in `block`, in place of the block pointers
+    pub fast_symlink: bool,
+    //every block the file owns, each indirect block right before the blocks it points to
+    pub block_list: Vec<BlockRef>,
+}
+
+/// The superblock of an ext3 journal, stored in its first block.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    pub version: u32,
+    pub block_size: u32,
+    //the journal is a circular log between first and max_len, block 0 is the superblock
+    pub max_len: u32,
+    pub first: u32,
+    //the sequence of the first transaction to replay, or of the next one when clean
+    pub sequence: u32,
+    //where that transaction starts, 0 when there is nothing to replay
+    pub start: u32,
+    pub errno: i32,
+    pub feature_compat: u32,
+    pub feature_incompat: u32,
+    pub feature_ro_compat: u32,
+}
+
+//the journal blocks of a committed transaction and the filesystem blocks they go to
+struct Transaction {
+    sequence: u32,
+    blocks: Vec<(u64, u64, bool)>,
+    revoked: Vec<u64>,
+}
+
+/// A block owned by a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockRef {
+    /// Holds the data at block `logical` of the file.
+    Data { logical: u64, block: u64 },
+    /// Holds pointers, `depth` levels above the data blocks (1 for the indirect block).
+    Indirect { depth: u64, block: u64 },
+}
+
+struct FileReader<'a> {
+    ext2: &'a Ext2<'a>,
+    inode_offset: u64,
+    size: u64,
+    position: u64,
 }
 
-impl Default for Ext2 {
-    fn default() -> Ext2 {
-        Ext2 {
+impl<'a> Ext2<'a> {
+    pub fn new(device: impl BlockDevice + 'a) -> Result<Ext2<'a>> {
+        let mut ext2 = Ext2 {
+            device: Box::new(device),
             volume_name: [0; 16],
             last_mounted: [0; 4],
             last_check: [0; 4],
@@ -83,448 +253,1976 @@ impl Default for Ext2 {
             first_data_block: 0,
             blocks_per_group: 0,
             frags_per_group: 0,
-        }
+            rev_level: 0,
+            feature_compat: 0,
+            feature_incompat: 0,
+            feature_ro_compat: 0,
+            journal_inode: 0,
+            group_descs: Vec::new(),
+        };
+        ext2.load_info()?;
+
+        return Ok(ext2);
     }
-}
 
-impl Filesystem for Ext2 {
-    fn load_info(&mut self, name: &str) -> &mut dyn Filesystem {
-        let mut opened_file = match File::open(&name) {
-            Err(why) => panic!("couldn't open {}: {}", name, why),
-            Ok(opened_file) => opened_file,
-        };
+    fn load_info(&mut self) -> Result<()> {
+        // ------------------------ REVISION ------------------------
+        let rev_level_temp: &mut [u8] = &mut [0; 4];
+        self.device.read_at(s_rev_level, rev_level_temp)?;
+        self.rev_level = LittleEndian::read_u32(rev_level_temp);
 
-        // ------------------------ INODE SIZE ------------------------
-        let inode_size_temp: &mut [u8] = &mut [0; 2];
-        utilities::seek_read(&mut opened_file, s_inode_size, inode_size_temp).unwrap();
-        self.inode_size = LittleEndian::read_u16(&inode_size_temp);
+        //revision 0 has fixed size inodes and no feature flags, the fields are just padding
+        if self.rev_level == 0 {
+            self.inode_size = 128;
+            self.first_inode = 11;
+        } else {
+            // ------------------------ INODE SIZE ------------------------
+            let inode_size_temp: &mut [u8] = &mut [0; 2];
+            self.device.read_at(s_inode_size, inode_size_temp)?;
+            self.inode_size = LittleEndian::read_u16(inode_size_temp);
+
+            // ------------------------ FIRST INODE ------------------------
+            let first_inode_temp: &mut [u8] = &mut [0; 4];
+            self.device.read_at(s_first_ino, first_inode_temp)?;
+            self.first_inode = LittleEndian::read_u32(first_inode_temp);
+
+            // ------------------------ FEATURES ------------------------
+            let features_temp: &mut [u8] = &mut [0; 4];
+            self.device.read_at(s_feature_compat, features_temp)?;
+            self.feature_compat = LittleEndian::read_u32(features_temp);
+            self.device.read_at(s_feature_incompat, features_temp)?;
+            self.feature_incompat = LittleEndian::read_u32(features_temp);
+            self.device.read_at(s_feature_ro_compat, features_temp)?;
+            self.feature_ro_compat = LittleEndian::read_u32(features_temp);
+
+            // ------------------------ JOURNAL INODE ------------------------
+            if self.feature_compat & COMPAT_HAS_JOURNAL != 0 {
+                let journal_inode_temp: &mut [u8] = &mut [0; 4];
+                self.device.read_at(s_journal_inum, journal_inode_temp)?;
+                self.journal_inode = LittleEndian::read_u32(journal_inode_temp);
+            }
+        }
 
         // ------------------------ NUM INODES ------------------------
         let num_inodes_temp: &mut [u8] = &mut [0; 4];
-        utilities::seek_read(&mut opened_file, s_inodes_count, num_inodes_temp).unwrap();
-        self.num_inodes = LittleEndian::read_u32(&num_inodes_temp);
-
-        // ------------------------ FIRST INODE ------------------------
-        let first_inode_temp: &mut [u8] = &mut [0; 4];
-        utilities::seek_read(&mut opened_file, s_first_ino, first_inode_temp).unwrap();
-        self.first_inode = LittleEndian::read_u32(&first_inode_temp);
+        self.device.read_at(s_inodes_count, num_inodes_temp)?;
+        self.num_inodes = LittleEndian::read_u32(num_inodes_temp);
 
         // ------------------------ INODES PER GROUP ------------------------
         let inodes_per_group_temp: &mut [u8] = &mut [0; 4];
-        utilities::seek_read(&mut opened_file, s_inodes_per_group, inodes_per_group_temp).unwrap();
-        self.inodes_per_group = LittleEndian::read_u32(&inodes_per_group_temp);
+        self.device
+            .read_at(s_inodes_per_group, inodes_per_group_temp)?;
+        self.inodes_per_group = LittleEndian::read_u32(inodes_per_group_temp);
 
         // ------------------------ FREE INODES ------------------------
         let free_inodes_temp: &mut [u8] = &mut [0; 4];
-        utilities::seek_read(&mut opened_file, s_free_inodes_count, free_inodes_temp).unwrap();
-        self.free_inodes = LittleEndian::read_u32(&free_inodes_temp);
+        self.device.read_at(s_free_inodes_count, free_inodes_temp)?;
+        self.free_inodes = LittleEndian::read_u32(free_inodes_temp);
 
         // ------------------------ BLOCK SIZE ------------------------
         let block_size_tmp: &mut [u8] = &mut [0; 4];
-        utilities::seek_read(&mut opened_file, s_log_block_size, block_size_tmp).unwrap();
-        self.block_size = 1024 << LittleEndian::read_u32(&block_size_tmp);
-        self.s_log_block_size = LittleEndian::read_u32(&block_size_tmp);
+        self.device.read_at(s_log_block_size, block_size_tmp)?;
+        self.s_log_block_size = LittleEndian::read_u32(block_size_tmp);
+        self.block_size = 1024 << self.s_log_block_size.min(6);
 
         // ------------------------ RESERVED BLOCKS ------------------------
         let reserved_blocks_count_temp: &mut [u8] = &mut [0; 4];
-        utilities::seek_read(
-            &mut opened_file,
-            s_r_blocks_count,
-            reserved_blocks_count_temp,
-        )
-        .unwrap();
-        self.reserved_blocks_count = LittleEndian::read_u32(&reserved_blocks_count_temp);
+        self.device
+            .read_at(s_r_blocks_count, reserved_blocks_count_temp)?;
+        self.reserved_blocks_count = LittleEndian::read_u32(reserved_blocks_count_temp);
 
         // ------------------------ FREE BLOCKS ------------------------
         let free_blocks_count_temp: &mut [u8] = &mut [0; 4];
-        utilities::seek_read(
-            &mut opened_file,
-            s_free_blocks_count,
-            free_blocks_count_temp,
-        )
-        .unwrap();
-        self.free_blocks_count = LittleEndian::read_u32(&free_blocks_count_temp);
+        self.device
+            .read_at(s_free_blocks_count, free_blocks_count_temp)?;
+        self.free_blocks_count = LittleEndian::read_u32(free_blocks_count_temp);
 
         // ------------------------ TOTAL BLOCKS ------------------------
         let num_blocks_temp: &mut [u8] = &mut [0; 4];
-        utilities::seek_read(&mut opened_file, s_blocks_count, num_blocks_temp).unwrap();
-        self.num_blocks = LittleEndian::read_u32(&num_blocks_temp);
+        self.device.read_at(s_blocks_count, num_blocks_temp)?;
+        self.num_blocks = LittleEndian::read_u32(num_blocks_temp);
 
         // ------------------------ FIRST DATA BLOCK ------------------------
         let first_data_block_temp: &mut [u8] = &mut [0; 4];
-        utilities::seek_read(&mut opened_file, s_first_data_block, first_data_block_temp).unwrap();
-        self.first_data_block = LittleEndian::read_u32(&first_data_block_temp);
+        self.device
+            .read_at(s_first_data_block, first_data_block_temp)?;
+        self.first_data_block = LittleEndian::read_u32(first_data_block_temp);
 
         // ------------------------ GROUP BLOCKS ------------------------
         let blocks_per_group_temp: &mut [u8] = &mut [0; 4];
-        utilities::seek_read(&mut opened_file, s_blocks_per_group, blocks_per_group_temp).unwrap();
-        self.blocks_per_group = LittleEndian::read_u32(&blocks_per_group_temp);
+        self.device
+            .read_at(s_blocks_per_group, blocks_per_group_temp)?;
+        self.blocks_per_group = LittleEndian::read_u32(blocks_per_group_temp);
 
         // ------------------------ FRAGS GROUP ------------------------
         let frags_per_group_temp: &mut [u8] = &mut [0; 4];
-        utilities::seek_read(&mut opened_file, s_frags_per_group, frags_per_group_temp).unwrap();
-        self.frags_per_group = LittleEndian::read_u32(&frags_per_group_temp);
+        self.device
+            .read_at(s_frags_per_group, frags_per_group_temp)?;
+        self.frags_per_group = LittleEndian::read_u32(frags_per_group_temp);
 
         // ------------------------ VOLUME NAME ------------------------
-        utilities::seek_read(&mut opened_file, s_volume_name, &mut self.volume_name).unwrap();
+        self.device.read_at(s_volume_name, &mut self.volume_name)?;
 
         // ------------------------ LAST CHECKED ------------------------
-        utilities::seek_read(&mut opened_file, s_lastcheck, &mut self.last_check).unwrap();
+        self.device.read_at(s_lastcheck, &mut self.last_check)?;
 
         // ------------------------ LAST MOUNTED ------------------------
-        utilities::seek_read(&mut opened_file, s_mtime, &mut self.last_mounted).unwrap();
+        self.device.read_at(s_mtime, &mut self.last_mounted)?;
 
         // ------------------------ LAST WRITE/EDIT ------------------------
-        utilities::seek_read(&mut opened_file, s_wtime, &mut self.last_write).unwrap();
-
-        return self;
-    }
-
-    fn print_info(self: &mut Self) -> &mut dyn Filesystem {
-        println!("\n------ Filesystem Information ------\n");
-        println!("Filesystem: EXT2\n");
-        println!("INFO INODE");
-        println!("Size Inode: {}", self.inode_size);
-        println!("Num Inode: {}", self.num_inodes);
-        println!("First inode: {}", self.first_inode);
-        println!("Inodes per group: {}", self.inodes_per_group);
-        println!("Free inodes: {}\n", self.free_inodes);
-
-        println!("BLOCK INFO");
-        println!("Block size: {}", self.block_size);
-        println!("Reserved blocks: {}", self.reserved_blocks_count);
-        println!("Free blocks: {}", self.free_blocks_count);
-
-        println!("Total blocks: {}", self.num_blocks);
-        println!("First data block: {}", self.first_data_block);
-        println!("Blocks per group: {}", self.blocks_per_group);
-        println!("Group frags: {}\n", self.frags_per_group);
-        println!("INFO VOLUME");
-        match str::from_utf8(&mut self.volume_name) {
-            Ok(v) => println!("Volume Name: {}", v),
-            Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
-        };
-        println!(
-            "Last Checked: {}",
-            utilities::convert_to_utc_time(self.last_check).format("%A %e %B %Y, %T"),
-        );
-        println!(
-            "Last Mounted: {}",
-            utilities::convert_to_utc_time(self.last_mounted).format("%A %e %B %Y, %T"),
-        );
-        println!(
-            "Last Write: {}\n",
-            utilities::convert_to_utc_time(self.last_write).format("%A %e %B %Y, %T"),
-        );
-        return self;
-    }
-
-    fn find(
-        self: &mut Self,
-        file_to_find: &str,
-        name_of_file: &str,
-        delete_flag: bool,
-    ) -> &mut dyn Filesystem {
-        let mut opened_file = match OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&name_of_file)
+        self.device.read_at(s_wtime, &mut self.last_write)?;
+
+        //these are divided by when locating inodes and blocks
+        if self.s_log_block_size > 6 || self.inodes_per_group == 0 || self.blocks_per_group == 0 {
+            return Err(Error::CorruptStructure(String::from(
+                "the superblock has an invalid block size or group size",
+            )));
+        }
+        if self.inode_size < 128
+            || !self.inode_size.is_power_of_two()
+            || self.inode_size as u32 > self.block_size
+        {
+            return Err(Error::CorruptStructure(format!(
+                "the superblock has an invalid inode size {}",
+                self.inode_size
+            )));
+        }
+
+        // ------------------------ GROUP DESCRIPTORS ------------------------
+        let num_groups = (self.num_blocks.saturating_sub(self.first_data_block) as u64)
+            .div_ceil(self.blocks_per_group as u64);
+        if num_groups * (self.inodes_per_group as u64) < self.num_inodes as u64 {
+            return Err(Error::CorruptStructure(format!(
+                "{} groups of {} inodes can't hold {} inodes",
+                num_groups, self.inodes_per_group, self.num_inodes
+            )));
+        }
+
+        for group in 0..num_groups {
+            let group_desc_temp: &mut [u8] = &mut [0; 32];
+            self.device
+                .read_at(get_group_desc_offset(self, group), group_desc_temp)?;
+            self.group_descs.push(GroupDesc {
+                block_bitmap: LittleEndian::read_u32(&group_desc_temp[0..4]),
+                inode_bitmap: LittleEndian::read_u32(&group_desc_temp[4..8]),
+                inode_table: LittleEndian::read_u32(&group_desc_temp[8..12]),
+                free_blocks_count: LittleEndian::read_u16(&group_desc_temp[12..14]),
+                free_inodes_count: LittleEndian::read_u16(&group_desc_temp[14..16]),
+                used_dirs_count: LittleEndian::read_u16(&group_desc_temp[16..18]),
+            });
+        }
+
+        return Ok(());
+    }
+
+    /// The whole inode of the file or directory at `path`, as `debugfs` shows it.
+    pub fn inode(&self, path: &str) -> Result<Inode> {
+        let dir_entry = lookup(self, path)?;
+        let number = LittleEndian::read_u32(&dir_entry.inode);
+        let inode_offset = get_inode_offset(self, number)?;
+
+        let raw: &mut [u8] = &mut [0; 128];
+        self.device.read_at(inode_offset, raw)?;
+
+        let mut block = [0; 15];
+        LittleEndian::read_u32_into(&raw[40..100], &mut block);
+
+        //a fast symlink keeps its target in i_block, and an extent mapped inode an extent
+        //tree, those are not block numbers
+        let fast_symlink = is_fast_symlink(self, inode_offset)?;
+        let mut block_list: Vec<BlockRef> = Vec::new();
+        if get_data_sectors(self, inode_offset)? > 0
+            && LittleEndian::read_u32(&raw[32..36]) & EXTENTS_FL == 0
         {
-            Err(why) => panic!("couldn't open {}: {}", name_of_file, why),
-            Ok(opened_file) => opened_file,
+            let mut logical = 0;
+            for (index, i_block) in block.iter().enumerate() {
+                let depth = if index < 12 { 0 } else { index as u64 - 11 };
+                get_block_list(self, *i_block as u64, depth, &mut logical, &mut block_list)?;
+            }
+        }
+
+        return Ok(Inode {
+            number,
+            mode: LittleEndian::read_u16(&raw[0..2]),
+            uid: LittleEndian::read_u16(&raw[2..4]) as u32
+                | (LittleEndian::read_u16(&raw[120..122]) as u32) << 16,
+            gid: LittleEndian::read_u16(&raw[24..26]) as u32
+                | (LittleEndian::read_u16(&raw[122..124]) as u32) << 16,
+            size: get_size(self, inode_offset)?,
+            atime: LittleEndian::read_u32(&raw[8..12]),
+            ctime: LittleEndian::read_u32(&raw[12..16]),
+            mtime: LittleEndian::read_u32(&raw[16..20]),
+            dtime: LittleEndian::read_u32(&raw[20..24]),
+            links_count: LittleEndian::read_u16(&raw[26..28]),
+            blocks: LittleEndian::read_u32(&raw[28..32]),
+            flags: LittleEndian::read_u32(&raw[32..36]),
+            version: LittleEndian::read_u32(&raw[36..40]),
+            generation: LittleEndian::read_u32(&raw[100..104]),
+            file_acl: LittleEndian::read_u32(&raw[104..108]),
+            faddr: LittleEndian::read_u32(&raw[112..116]),
+            frag: raw[116],
+            fsize: raw[117],
+            block,
+            fast_symlink,
+            block_list,
+        });
+    }
+
+    /// The superblock of the journal, on volumes with a journal inode of their own.
+    pub fn journal(&self) -> Result<Journal> {
+        if self.feature_compat & COMPAT_HAS_JOURNAL == 0 || self.journal_inode == 0 {
+            return Err(Error::Unsupported(String::from(
+                "the volume has no journal of its own",
+            )));
+        }
+
+        return read_journal(self);
+    }
+
+    /// Writes the committed transactions of the journal to the filesystem and empties the
+    /// journal, returning how many there were.
+    pub fn replay_journal(&mut self) -> Result<usize> {
+        return replay_journal(self);
+    }
+}
+
+impl<'a> Filesystem for Ext2<'a> {
+    fn volume_info(&self) -> VolumeInfo {
+        let inode_info = vec![
+            (String::from("Size Inode"), self.inode_size.to_string()),
+            (String::from("Num Inode"), self.num_inodes.to_string()),
+            (String::from("First inode"), self.first_inode.to_string()),
+            (
+                String::from("Inodes per group"),
+                self.inodes_per_group.to_string(),
+            ),
+            (String::from("Free inodes"), self.free_inodes.to_string()),
+        ];
+
+        let block_info = vec![
+            (String::from("Block size"), self.block_size.to_string()),
+            (
+                String::from("Reserved blocks"),
+                self.reserved_blocks_count.to_string(),
+            ),
+            (
+                String::from("Free blocks"),
+                self.free_blocks_count.to_string(),
+            ),
+            (String::from("Total blocks"), self.num_blocks.to_string()),
+            (
+                String::from("First data block"),
+                self.first_data_block.to_string(),
+            ),
+            (
+                String::from("Blocks per group"),
+                self.blocks_per_group.to_string(),
+            ),
+            (
+                String::from("Group frags"),
+                self.frags_per_group.to_string(),
+            ),
+        ];
+
+        let volume_info = vec![
+            (
+                String::from("Volume Name"),
+                String::from_utf8_lossy(&self.volume_name).to_string(),
+            ),
+            (
+                String::from("Last Checked"),
+                utilities::convert_to_utc_time(self.last_check)
+                    .format("%A %e %B %Y, %T")
+                    .to_string(),
+            ),
+            (
+                String::from("Last Mounted"),
+                utilities::convert_to_utc_time(self.last_mounted)
+                    .format("%A %e %B %Y, %T")
+                    .to_string(),
+            ),
+            (
+                String::from("Last Write"),
+                utilities::convert_to_utc_time(self.last_write)
+                    .format("%A %e %B %Y, %T")
+                    .to_string(),
+            ),
+        ];
+
+        let mut sections = vec![
+            InfoSection {
+                title: String::from("INFO INODE"),
+                fields: inode_info,
+            },
+            InfoSection {
+                title: String::from("BLOCK INFO"),
+                fields: block_info,
+            },
+            InfoSection {
+                title: String::from("INFO VOLUME"),
+                fields: volume_info,
+            },
+            InfoSection {
+                title: String::from("FEATURES"),
+                fields: vec![
+                    (
+                        String::from("Revision"),
+                        match self.rev_level {
+                            0 => String::from("0 (original)"),
+                            1 => String::from("1 (dynamic)"),
+                            rev_level => rev_level.to_string(),
+                        },
+                    ),
+                    (
+                        String::from("Features"),
+                        match get_feature_names(
+                            self.feature_compat,
+                            self.feature_incompat,
+                            self.feature_ro_compat,
+                        ) {
+                            names if names.is_empty() => String::from("(none)"),
+                            names => names.join(" "),
+                        },
+                    ),
+                ],
+            },
+        ];
+
+        if self.feature_compat & COMPAT_HAS_JOURNAL != 0 {
+            sections.push(InfoSection {
+                title: String::from("JOURNAL"),
+                fields: get_journal_info(self),
+            });
+        }
+
+        for (group, group_desc) in self.group_descs.iter().enumerate() {
+            let first_block =
+                self.first_data_block as u64 + group as u64 * self.blocks_per_group as u64;
+            let last_block =
+                (first_block + self.blocks_per_group as u64).min(self.num_blocks as u64) - 1;
+
+            sections.push(InfoSection {
+                title: format!("GROUP {}", group),
+                fields: vec![
+                    (
+                        String::from("Blocks"),
+                        format!("{}-{}", first_block, last_block),
+                    ),
+                    (
+                        String::from("Block bitmap"),
+                        group_desc.block_bitmap.to_string(),
+                    ),
+                    (
+                        String::from("Inode bitmap"),
+                        group_desc.inode_bitmap.to_string(),
+                    ),
+                    (
+                        String::from("Inode table"),
+                        group_desc.inode_table.to_string(),
+                    ),
+                    (
+                        String::from("Free blocks"),
+                        group_desc.free_blocks_count.to_string(),
+                    ),
+                    (
+                        String::from("Free inodes"),
+                        group_desc.free_inodes_count.to_string(),
+                    ),
+                    (
+                        String::from("Directories"),
+                        group_desc.used_dirs_count.to_string(),
+                    ),
+                ],
+            });
+        }
+
+        //EXT3 is EXT2 with a journal
+        return VolumeInfo {
+            filesystem: if self.feature_compat & COMPAT_HAS_JOURNAL != 0 {
+                String::from("EXT3")
+            } else {
+                String::from("EXT2")
+            },
+            sections,
         };
+    }
+
+    fn stat(&self, path: &str) -> Result<Metadata> {
+        return get_metadata(self, &lookup(self, path)?);
+    }
 
-        let offset_inode = get_inode_offset(self, &opened_file, 2);
+    fn read_dir(&self, path: &str) -> Result<Vec<Metadata>> {
+        let dir_entry = lookup(self, path)?;
+        if get_file_type(self, &dir_entry)? != FileType::Directory {
+            return Err(Error::Unsupported(format!(
+                "{} is not a directory, only directories can be listed",
+                path
+            )));
+        }
+
+        let mut entries: Vec<Metadata> = Vec::new();
+        for dir_entry in read_dir_entries(self, LittleEndian::read_u32(&dir_entry.inode))? {
+            entries.push(get_metadata(self, &dir_entry)?);
+        }
 
-        let num_data_blocks = get_data_blocks(self, &opened_file, offset_inode);
+        return Ok(entries);
+    }
 
-        let mut block_counter = 0;
+    fn open(&self, path: &str) -> Result<Box<dyn Read + '_>> {
+        let dir_entry = lookup(self, path)?;
+        if get_file_type(self, &dir_entry)? == FileType::Directory {
+            return Err(Error::Unsupported(format!(
+                "{} is a directory, only files can be read",
+                path
+            )));
+        }
 
-        let mut found;
-        loop {
-            found = find_file(
-                self,
-                &mut opened_file,
-                offset_inode,
-                file_to_find,
-                delete_flag,
-                block_counter,
-            );
-            block_counter += 1;
+        let inode_offset = get_inode_offset(self, LittleEndian::read_u32(&dir_entry.inode))?;
+
+        return Ok(Box::new(FileReader {
+            ext2: self,
+            inode_offset,
+            size: get_size(self, inode_offset)?,
+            position: 0,
+        }));
+    }
+
+    fn remove(&mut self, path: &str, scrub_flag: bool) -> Result<()> {
+        check_writable(self)?;
+        let dir_entry = lookup(self, path)?;
+        if get_file_type(self, &dir_entry)? == FileType::Directory {
+            return Err(Error::Unsupported(format!(
+                "{} is a directory, only files can be deleted",
+                path
+            )));
+        }
+
+        //everything the inode owns is found and checked before the first write, so a corrupt
+        //inode is refused instead of being left half deleted
+        let inode = LittleEndian::read_u32(&dir_entry.inode);
+        let (blocks, file_acl) = get_owned_blocks(self, get_inode_offset(self, inode)?)?;
+
+        match dir_entry.prev_offset {
+            Some(prev_offset) => {
+                //the previous entry absorbs the space of the deleted one
+                let sum =
+                    get_u16(self, prev_offset + 4)? + LittleEndian::read_u16(&dir_entry.rec_len);
 
-            if num_data_blocks <= block_counter || found {
-                break;
+                self.device.write_at(prev_offset + 4, &sum.to_le_bytes())?;
             }
+            None => {
+                //The first entry in the block can't be merged, so it is marked as unused
+                self.device.write_at(dir_entry.offset, &[0; 4])?;
+            }
+        }
+
+        unlink_inode(self, inode, &blocks, file_acl, scrub_flag)?;
+
+        return Ok(());
+    }
+
+    fn case_sensitive(&self) -> bool {
+        return true;
+    }
+}
+
+impl<'a> Read for FileReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
         }
 
-        if !found {
-            println!("could not find the file :(");
+        //never read past the end of the current block or the end of the file
+        let block_size = self.ext2.block_size as u64;
+        let offset_in_block = self.position % block_size;
+        let to_read = (buf.len() as u64)
+            .min(block_size - offset_in_block)
+            .min(self.size - self.position) as usize;
+
+        let data_block = get_data_block(self.ext2, self.inode_offset, self.position / block_size)?;
+        if data_block == 0 {
+            //a hole in a sparse file reads as zeros
+            buf[..to_read].iter_mut().for_each(|b| *b = 0);
+        } else {
+            self.ext2.device.read_at(
+                data_block * block_size + offset_in_block,
+                &mut buf[..to_read],
+            )?;
         }
+        self.position += to_read as u64;
 
-        return self;
+        return Ok(to_read);
     }
 }
 
-fn find_file(
-    ext2: &Ext2,
-    opened_file: &File,
-    offset_inode: u64,
-    file_to_find: &str,
-    delete_flag: bool,
+struct BlockMap<'a> {
+    ext2: &'a Ext2<'a>,
+    inode_offset: u64,
+    num_blocks: u64,
     block_counter: u64,
-) -> bool {
-    let data_block_offset = get_data_block_offset(opened_file, offset_inode, block_counter);
+}
 
-    //Lastly, Read the data at the start of the block until we find 0's for the rec len
-    let mut dir_entry: DirEntry = DirEntry::default();
+impl<'a> Iterator for BlockMap<'a> {
+    type Item = Result<u64>;
 
-    let data_offset: u64 = (data_block_offset * ext2.block_size as u64).into();
+    fn next(&mut self) -> Option<Result<u64>> {
+        if self.block_counter >= self.num_blocks {
+            return None;
+        }
 
-    let mut bytes_read: u64 = 0;
+        let data_block = get_data_block(self.ext2, self.inode_offset, self.block_counter);
+        self.block_counter += 1;
 
-    let mut index_offset = 0;
-    let mut array_rec_len: [u16; 2] = [0xFF; 2];
+        return Some(data_block);
+    }
+}
 
-    loop {
-        fill_dir_entry(&opened_file, data_offset, bytes_read, &mut dir_entry);
+fn get_block_map<'a>(ext2: &'a Ext2, inode_offset: u64) -> Result<BlockMap<'a>> {
+    //i_blocks also counts the indirect blocks, so the number of data blocks comes from the size
+    let block_size = ext2.block_size as u64;
+    let num_blocks = get_size(ext2, inode_offset)?.div_ceil(block_size);
+
+    return Ok(BlockMap {
+        ext2,
+        inode_offset,
+        num_blocks,
+        block_counter: 0,
+    });
+}
 
-        array_rec_len[index_offset % 2] = LittleEndian::read_u16(&dir_entry.rec_len);
-        index_offset += 1;
+fn lookup(ext2: &Ext2, path: &str) -> Result<DirEntry> {
+    //the root directory has no entry of its own, it is always inode 2
+    let mut dir_entry = DirEntry {
+        inode: 2u32.to_le_bytes(),
+        file_type: [2],
+        ..Default::default()
+    };
+
+    for component in utilities::split_path(path) {
+        if get_file_type(ext2, &dir_entry)? != FileType::Directory {
+            return Err(Error::NotFound(path.to_string()));
+        }
 
-        if file_to_find.eq_ignore_ascii_case(str::from_utf8(&dir_entry.name).unwrap())
-            && dir_entry.file_type[0] != 2
-        {
-            let offset_inode_file =
-                get_inode_offset(ext2, opened_file, LittleEndian::read_u32(&dir_entry.inode));
+        dir_entry = read_dir_entries(ext2, LittleEndian::read_u32(&dir_entry.inode))?
+            .into_iter()
+            .find(|dir_entry| dir_entry.name == component.as_bytes())
+            .ok_or_else(|| Error::NotFound(path.to_string()))?;
+    }
 
-            let size_file = get_size(opened_file, offset_inode_file);
+    return Ok(dir_entry);
+}
 
-            if delete_flag == false {
-                //println!("Blocks data of file: {}", block_counter);
-                println!("Found the file! File size: {}", size_file);
-            } else {
-                if block_counter == 0 {
-                    //println!("We wanna delete first block");
-
-                    //get rec length of current file and save it
-                    let current_rec_len = LittleEndian::read_u16(&dir_entry.rec_len);
-                    //get rec length of the previous file
-                    let rec_len_prev = array_rec_len[index_offset % 2];
-
-                    let sum = current_rec_len + rec_len_prev;
-
-                    utilities::seek_write(
-                        opened_file,
-                        (data_offset + bytes_read - rec_len_prev as u64 + 4).into(),
-                        &mut sum.to_le_bytes(),
-                    )
-                    .unwrap();
-                } else {
-                    if array_rec_len[1] == 0xFF {
-                        //The first file in the block
-                        //Get the rec length of current file
-                        let new_offset_from_base =
-                            bytes_read + (LittleEndian::read_u16(&dir_entry.rec_len) as u64);
-                        //Go to next file and get its dir entry
-                        let mut dir_entry_next: DirEntry = DirEntry::default();
-
-                        fill_dir_entry(
-                            &opened_file,
-                            data_offset,
-                            new_offset_from_base,
-                            &mut dir_entry_next,
-                        );
-
-                        //write the whole dir entry into the start offset of the current file
-                        utilities::seek_write(
-                            opened_file,
-                            (data_offset).into(),
-                            &mut dir_entry_next.inode,
-                        )
-                        .unwrap();
-
-                        let sum = LittleEndian::read_u16(&dir_entry_next.rec_len)
-                            + LittleEndian::read_u16(&dir_entry.rec_len);
-
-                        utilities::seek_write(
-                            opened_file,
-                            (data_offset + 4).into(),
-                            &mut sum.to_le_bytes(),
-                        )
-                        .unwrap();
-
-                        utilities::seek_write(
-                            opened_file,
-                            (data_offset + 6).into(),
-                            &mut dir_entry_next.name_len,
-                        )
-                        .unwrap();
-
-                        utilities::seek_write(
-                            opened_file,
-                            (data_offset + 7).into(),
-                            &mut dir_entry_next.file_type,
-                        )
-                        .unwrap();
-
-                        utilities::seek_write(
-                            opened_file,
-                            (data_offset + 8).into(),
-                            &mut dir_entry_next.name,
-                        )
-                        .unwrap();
-                    } else {
-                        //println!("File NOT first in block");
-                        //get rec length of current file and save it
-                        let current_rec_len = LittleEndian::read_u16(&dir_entry.rec_len);
-                        //get rec length of the previous file
-                        let rec_len_prev = array_rec_len[index_offset % 2];
-
-                        let sum = current_rec_len + rec_len_prev;
-
-                        utilities::seek_write(
-                            opened_file,
-                            (data_offset + bytes_read - rec_len_prev as u64 + 4).into(),
-                            &mut sum.to_le_bytes(),
-                        )
-                        .unwrap();
-                    }
+fn read_dir_entries(ext2: &Ext2, inode: u32) -> Result<Vec<DirEntry>> {
+    let offset_inode = get_inode_offset(ext2, inode)?;
+    let mut dir_entries: Vec<DirEntry> = Vec::new();
+
+    for data_block in get_block_map(ext2, offset_inode)? {
+        let data_block = data_block?;
+        if data_block == 0 {
+            //directories don't have holes, but a corrupt one shouldn't make us read the boot block
+            continue;
+        }
+
+        //Read the data at the start of the block, entry by entry, until the end of the block
+        let data_offset: u64 = data_block * ext2.block_size as u64;
+        let mut bytes_read: u64 = 0;
+        let mut prev_offset: Option<u64> = None;
+
+        while bytes_read < ext2.block_size as u64 {
+            let mut dir_entry: DirEntry = DirEntry::default();
+            fill_dir_entry(ext2, data_offset, bytes_read, &mut dir_entry)?;
+
+            let rec_len = LittleEndian::read_u16(&dir_entry.rec_len) as u64;
+            if rec_len < 8 || bytes_read + rec_len > ext2.block_size as u64 {
+                //we can't know where the next entry starts
+                return Err(Error::CorruptStructure(format!(
+                    "directory entry with invalid rec_len {} in block {}",
+                    rec_len, data_block
+                )));
+            }
+
+            //unused entries have inode 0, and . and .. are not real children of the directory
+            if LittleEndian::read_u32(&dir_entry.inode) != 0
+                && dir_entry.name != b"."
+                && dir_entry.name != b".."
+            {
+                dir_entry.offset = data_offset + bytes_read;
+                dir_entry.prev_offset = prev_offset;
+                dir_entries.push(dir_entry);
+            }
+
+            prev_offset = Some(data_offset + bytes_read);
+            bytes_read += rec_len;
+        }
+    }
+
+    return Ok(dir_entries);
+}
+
+fn get_feature_names(compat: u32, incompat: u32, ro_compat: u32) -> Vec<String> {
+    let mut names = Vec::new();
+    for (features, known, prefix) in [
+        (compat, &COMPAT_FEATURES[..], "C"),
+        (incompat, &INCOMPAT_FEATURES[..], "I"),
+        (ro_compat, &RO_COMPAT_FEATURES[..], "R"),
+    ] {
+        for bit in 0..32 {
+            let mask = 1 << bit;
+            if features & mask == 0 {
+                continue;
+            }
+            //bits nobody has named yet are shown the way e2fsprogs does
+            match known.iter().find(|(flag, _)| *flag == mask) {
+                Some((_, name)) => names.push(name.to_string()),
+                None => names.push(format!("FEATURE_{}{}", prefix, bit)),
+            }
+        }
+    }
+
+    return names;
+}
+
+//writing to a filesystem with features we don't maintain would leave it inconsistent
+fn check_writable(ext2: &Ext2) -> Result<()> {
+    //the transactions in the journal would be replayed over our changes on the next mount
+    if ext2.feature_compat & COMPAT_HAS_JOURNAL != 0 {
+        //a journal on another device can't be read, only the superblock flag tells about it
+        let mut needs_recovery = ext2.feature_incompat & INCOMPAT_RECOVER != 0;
+        if !needs_recovery && ext2.journal_inode != 0 {
+            match read_journal(ext2) {
+                Ok(journal) => needs_recovery = journal.start != 0,
+                Err(e) => {
+                    return Err(Error::Unsupported(format!(
+                        "the journal can't be read to tell if it needs recovery, {}",
+                        e
+                    )))
                 }
-                println!("File succesfully deleted!");
             }
+        }
+
+        if needs_recovery {
+            return Err(Error::Unsupported(String::from(
+                "the journal needs recovery, replay it into a copy with /replay or run e2fsck first",
+            )));
+        }
+    }
+
+    let incompat = ext2.feature_incompat & !WRITE_INCOMPAT;
+    let ro_compat = ext2.feature_ro_compat & !WRITE_RO_COMPAT;
+    if incompat == 0 && ro_compat == 0 {
+        return Ok(());
+    }
+
+    return Err(Error::Unsupported(format!(
+        "the filesystem has features that can't be written: {}",
+        get_feature_names(0, incompat, ro_compat).join(" ")
+    )));
+}
+
+fn get_journal_info(ext2: &Ext2) -> Vec<(String, String)> {
+    if ext2.journal_inode == 0 {
+        return vec![(String::from("Journal"), String::from("on another device"))];
+    }
+
+    let mut fields = vec![(
+        String::from("Journal inode"),
+        ext2.journal_inode.to_string(),
+    )];
+    let journal = match read_journal(ext2) {
+        Ok(journal) => journal,
+        Err(e) => {
+            fields.push((String::from("State"), format!("unreadable, {}", e)));
+            return fields;
+        }
+    };
+
+    let mut features: Vec<String> = Vec::new();
+    for (flags, known) in [
+        (journal.feature_compat, &JOURNAL_COMPAT_FEATURES[..]),
+        (journal.feature_incompat, &JOURNAL_INCOMPAT_FEATURES[..]),
+    ] {
+        for (flag, name) in known {
+            if flags & flag != 0 {
+                features.push(name.to_string());
+            }
+        }
+    }
+
+    fields.push((String::from("Version"), journal.version.to_string()));
+    fields.push((
+        String::from("Size"),
+        format!("{} blocks of {} bytes", journal.max_len, journal.block_size),
+    ));
+    fields.push((
+        String::from("Features"),
+        if features.is_empty() {
+            String::from("(none)")
+        } else {
+            features.join(" ")
+        },
+    ));
+    fields.push((String::from("Sequence"), journal.sequence.to_string()));
+    fields.push((
+        String::from("State"),
+        match (journal.start, journal.errno) {
+            (_, errno) if errno != 0 => format!("aborted with error {}", errno),
+            (0, _) => String::from("clean"),
+            (start, _) => format!(
+                "needs recovery, transaction {} starts at block {}",
+                journal.sequence, start
+            ),
+        },
+    ));
+
+    return fields;
+}
 
-            return true;
-        } else if dir_entry.file_type[0] == 2
-            && str::from_utf8(&dir_entry.name).unwrap().ne("lost+found")
-            && str::from_utf8(&dir_entry.name).unwrap().ne(".")
-            && str::from_utf8(&dir_entry.name).unwrap().ne("..")
+fn get_journal_blocks(ext2: &Ext2) -> Result<Vec<u64>> {
+    //the journal is an ordinary file, its blocks are found through its inode
+    let inode_offset = get_inode_offset(ext2, ext2.journal_inode)?;
+    let blocks = get_block_map(ext2, inode_offset)?.collect::<Result<Vec<u64>>>()?;
+    if blocks.is_empty() || blocks.contains(&0) {
+        return Err(Error::CorruptStructure(String::from(
+            "the journal inode is empty or has holes",
+        )));
+    }
+
+    return Ok(blocks);
+}
+
+fn read_journal(ext2: &Ext2) -> Result<Journal> {
+    let journal_blocks = get_journal_blocks(ext2)?;
+    let superblock: &mut [u8] = &mut [0; 1024];
+    ext2.device
+        .read_at(journal_blocks[0] * ext2.block_size as u64, superblock)?;
+
+    let version = match BigEndian::read_u32(&superblock[4..8]) {
+        JBD2_SUPERBLOCK_V1 => 1,
+        JBD2_SUPERBLOCK_V2 => 2,
+        _ => 0,
+    };
+    if BigEndian::read_u32(&superblock[0..4]) != JBD2_MAGIC || version == 0 {
+        return Err(Error::CorruptStructure(String::from(
+            "the first block of the journal is not a journal superblock",
+        )));
+    }
+
+    //version 1 has no feature flags, the bytes are padding
+    let feature = |offset: usize| match version {
+        1 => 0,
+        _ => BigEndian::read_u32(&superblock[offset..offset + 4]),
+    };
+    let journal = Journal {
+        version,
+        block_size: BigEndian::read_u32(&superblock[12..16]),
+        max_len: BigEndian::read_u32(&superblock[16..20]),
+        first: BigEndian::read_u32(&superblock[20..24]),
+        sequence: BigEndian::read_u32(&superblock[24..28]),
+        start: BigEndian::read_u32(&superblock[28..32]),
+        errno: BigEndian::read_i32(&superblock[32..36]),
+        feature_compat: feature(36),
+        feature_incompat: feature(40),
+        feature_ro_compat: feature(44),
+    };
+
+    if journal.block_size != ext2.block_size
+        || journal.max_len as usize > journal_blocks.len()
+        || journal.first == 0
+        || journal.first >= journal.max_len
+        || journal.start >= journal.max_len
+    {
+        return Err(Error::CorruptStructure(String::from(
+            "the journal superblock doesn't match the journal inode",
+        )));
+    }
+
+    return Ok(journal);
+}
+
+//the committed transactions from the start of the journal, in order
+fn read_transactions(
+    ext2: &Ext2,
+    journal: &Journal,
+    journal_blocks: &[u64],
+) -> Result<Vec<Transaction>> {
+    let block_size = ext2.block_size as usize;
+    //the log wraps around from the end of the journal to its first block
+    let next = |block: u64| match block + 1 {
+        next if next >= journal.max_len as u64 => journal.first as u64,
+        next => next,
+    };
+
+    let mut transactions: Vec<Transaction> = Vec::new();
+    let mut transaction = Transaction {
+        sequence: journal.sequence,
+        blocks: Vec::new(),
+        revoked: Vec::new(),
+    };
+    let mut block = journal.start as u64;
+    let journal_block: &mut [u8] = &mut vec![0; block_size];
+
+    //every block is visited at most once, a journal that never ends is corrupt anyway
+    for _ in 0..journal.max_len {
+        ext2.device.read_at(
+            journal_blocks[block as usize] * block_size as u64,
+            journal_block,
+        )?;
+
+        //the log ends at the first block that doesn't belong to the expected transaction
+        if BigEndian::read_u32(&journal_block[0..4]) != JBD2_MAGIC
+            || BigEndian::read_u32(&journal_block[8..12]) != transaction.sequence
         {
-            let mut inner_block_counter = 0;
-            let mut found;
-
-            //recalculate offset inode
-            let offset_inode =
-                get_inode_offset(ext2, &opened_file, LittleEndian::read_u32(&dir_entry.inode));
-            let blocks_data_file = get_data_blocks(ext2, opened_file, offset_inode);
-            loop {
-                found = find_file(
-                    ext2,
-                    opened_file,
-                    offset_inode,
-                    file_to_find,
-                    delete_flag,
-                    inner_block_counter,
-                );
-                inner_block_counter += 1;
-                if blocks_data_file <= inner_block_counter || found {
-                    break;
+            break;
+        }
+
+        match BigEndian::read_u32(&journal_block[4..8]) {
+            JBD2_DESCRIPTOR_BLOCK => {
+                //each tag names the filesystem block of one of the journal blocks that follow
+                let mut offset = 12;
+                while offset + 8 <= block_size {
+                    let target = BigEndian::read_u32(&journal_block[offset..offset + 4]) as u64;
+                    let flags = BigEndian::read_u16(&journal_block[offset + 6..offset + 8]);
+                    offset += 8;
+                    if flags & JBD2_FLAG_SAME_UUID == 0 {
+                        offset += 16;
+                    }
+
+                    block = next(block);
+                    transaction.blocks.push((
+                        target,
+                        journal_blocks[block as usize],
+                        flags & JBD2_FLAG_ESCAPE != 0,
+                    ));
+                    if flags & JBD2_FLAG_LAST_TAG != 0 {
+                        break;
+                    }
+                }
+            }
+            JBD2_COMMIT_BLOCK => {
+                let sequence = transaction.sequence.wrapping_add(1);
+                transactions.push(transaction);
+                transaction = Transaction {
+                    sequence,
+                    blocks: Vec::new(),
+                    revoked: Vec::new(),
+                };
+            }
+            JBD2_REVOKE_BLOCK => {
+                //the size in bytes of the records, header included
+                let size = (BigEndian::read_u32(&journal_block[12..16]) as usize).min(block_size);
+                for record in journal_block[16..size.max(16)].chunks_exact(4) {
+                    transaction.revoked.push(BigEndian::read_u32(record) as u64);
                 }
             }
+            _ => break,
+        }
+
+        block = next(block);
+    }
+
+    return Ok(transactions);
+}
 
-            bytes_read = bytes_read + (LittleEndian::read_u16(&dir_entry.rec_len) as u64);
+fn replay_journal(ext2: &mut Ext2) -> Result<usize> {
+    let journal = ext2.journal()?;
+    let journal_blocks = get_journal_blocks(ext2)?;
+    let mut transactions: Vec<Transaction> = Vec::new();
+    if journal.start != 0 {
+        let unknown = journal.feature_incompat & !REPLAY_JOURNAL_INCOMPAT;
+        if unknown != 0 {
+            return Err(Error::Unsupported(format!(
+                "the journal has features that can't be replayed: 0x{:x}",
+                unknown
+            )));
+        }
+        transactions = read_transactions(ext2, &journal, &journal_blocks)?;
+    }
+
+    //a revoked block is not replayed from its transaction or any earlier one
+    let mut revoked: HashMap<u64, u32> = HashMap::new();
+    for transaction in &transactions {
+        for block in &transaction.revoked {
+            revoked.insert(*block, transaction.sequence);
+        }
+    }
 
-            if bytes_read >= ext2.block_size as u64 || found {
-                return found;
+    let block_size = ext2.block_size as u64;
+    let data: &mut [u8] = &mut vec![0; block_size as usize];
+    for transaction in &transactions {
+        for (target, journal_block, escaped) in &transaction.blocks {
+            if revoked
+                .get(target)
+                .is_some_and(|sequence| *sequence >= transaction.sequence)
+            {
+                continue;
             }
-        } else {
-            bytes_read = bytes_read + (LittleEndian::read_u16(&dir_entry.rec_len) as u64);
 
-            if bytes_read >= ext2.block_size as u64 {
-                return false;
+            ext2.device.read_at(journal_block * block_size, data)?;
+            //blocks that started with the journal magic were stored with it zeroed
+            if *escaped {
+                BigEndian::write_u32(&mut data[0..4], JBD2_MAGIC);
             }
+            ext2.device
+                .write_at(check_block(ext2, *target)? * block_size, data)?;
         }
     }
+
+    //the journal is empty now. Like jbd2, one sequence number is skipped after the last
+    //transaction, so what is left of an unfinished one is never taken for the next
+    let sequence = match transactions.last() {
+        Some(transaction) => transaction.sequence.wrapping_add(2),
+        None => journal.sequence,
+    };
+    let journal_superblock = journal_blocks[0] * block_size;
+    ext2.device
+        .write_at(journal_superblock + 24, &sequence.to_be_bytes())?;
+    ext2.device
+        .write_at(journal_superblock + 28, &0u32.to_be_bytes())?;
+
+    let incompat = get_u32(ext2, s_feature_incompat)? & !INCOMPAT_RECOVER;
+    ext2.device
+        .write_at(s_feature_incompat, &incompat.to_le_bytes())?;
+    ext2.feature_incompat = incompat;
+
+    return Ok(transactions.len());
+}
+
+fn get_file_type(ext2: &Ext2, dir_entry: &DirEntry) -> Result<FileType> {
+    //without the filetype feature the type is only in the mode of the inode, and the
+    //byte after name_len is the high byte of the name length
+    let file_type = if ext2.feature_incompat & INCOMPAT_FILETYPE != 0 {
+        dir_entry.file_type[0]
+    } else {
+        0
+    };
+    let file_type = match file_type {
+        0 => {
+            let offset_inode = get_inode_offset(ext2, LittleEndian::read_u32(&dir_entry.inode))?;
+            match get_u16(ext2, offset_inode)? & 0xF000 {
+                0x8000 => 1,
+                0x4000 => 2,
+                0xA000 => 7,
+                _ => 0,
+            }
+        }
+        file_type => file_type,
+    };
+
+    return Ok(match file_type {
+        1 => FileType::File,
+        2 => FileType::Directory,
+        7 => FileType::Symlink,
+        _ => FileType::Other,
+    });
+}
+
+fn get_metadata(ext2: &Ext2, dir_entry: &DirEntry) -> Result<Metadata> {
+    let inode = LittleEndian::read_u32(&dir_entry.inode);
+    let offset_inode = get_inode_offset(ext2, inode)?;
+
+    //uid and gid have 16 more bits in the OS dependent part of the inode
+    let uid =
+        get_u16(ext2, offset_inode + 2)? as u32 | (get_u16(ext2, offset_inode + 120)? as u32) << 16;
+    let gid = get_u16(ext2, offset_inode + 24)? as u32
+        | (get_u16(ext2, offset_inode + 122)? as u32) << 16;
+    let mtime = utilities::convert_to_utc_time(get_u32(ext2, offset_inode + 16)?.to_le_bytes());
+
+    return Ok(Metadata {
+        name: String::from_utf8_lossy(&dir_entry.name).to_string(),
+        short_name: None,
+        file_type: get_file_type(ext2, dir_entry)?,
+        size: get_size(ext2, offset_inode)?,
+        id: inode as u64,
+        modified: Some(mtime.naive_utc()),
+        details: vec![
+            (String::from("Inode"), inode.to_string()),
+            (
+                String::from("Mode"),
+                get_mode_string(get_u16(ext2, offset_inode)?),
+            ),
+            (
+                String::from("Links"),
+                get_u16(ext2, offset_inode + 26)?.to_string(),
+            ),
+            (String::from("UID"), uid.to_string()),
+            (String::from("GID"), gid.to_string()),
+        ],
+    });
+}
+
+fn get_mode_string(mode: u16) -> String {
+    //the same notation as ls -l: the type, then rwx for owner, group and others
+    let mut mode_string = String::new();
+    mode_string.push(match mode & 0xF000 {
+        0x4000 => 'd',
+        0xA000 => 'l',
+        0x2000 => 'c',
+        0x6000 => 'b',
+        0x1000 => 'p',
+        0xC000 => 's',
+        _ => '-',
+    });
+
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 7;
+        mode_string.push(if bits & 4 == 4 { 'r' } else { '-' });
+        mode_string.push(if bits & 2 == 2 { 'w' } else { '-' });
+        mode_string.push(if bits & 1 == 1 { 'x' } else { '-' });
+    }
+
+    //setuid, setgid and sticky replace the execute flag they go with
+    for (bit, index, set, unset) in [
+        (0x800, 3, 's', 'S'),
+        (0x400, 6, 's', 'S'),
+        (0x200, 9, 't', 'T'),
+    ] {
+        if mode & bit == bit {
+            let executable = mode_string.as_bytes()[index] == b'x';
+            mode_string.replace_range(
+                index..=index,
+                &(if executable { set } else { unset }).to_string(),
+            );
+        }
+    }
+
+    return mode_string;
+}
+
+fn get_data_block(ext2: &Ext2, inode_offset: u64, block_counter: u64) -> Result<u64> {
+    //i_block[0..12] point to data, i_block[12], [13] and [14] to the single, double
+    //and triple indirect blocks, which are blocks full of 4 byte block numbers
+    if get_u32(ext2, inode_offset + 32)? & EXTENTS_FL != 0 {
+        return Err(Error::Unsupported(String::from(
+            "the file is mapped with extents, only block maps can be read",
+        )));
+    }
+
+    let pointers_per_block = ext2.block_size as u64 / 4;
+    let mut index = block_counter;
+
+    if index < 12 {
+        return get_i_block(ext2, inode_offset, index);
+    }
+    index -= 12;
+
+    if index < pointers_per_block {
+        let indirect = get_i_block(ext2, inode_offset, 12)?;
+        return get_block_pointer(ext2, indirect, index);
+    }
+    index -= pointers_per_block;
+
+    if index < pointers_per_block * pointers_per_block {
+        let double_indirect = get_i_block(ext2, inode_offset, 13)?;
+        let indirect = get_block_pointer(ext2, double_indirect, index / pointers_per_block)?;
+        return get_block_pointer(ext2, indirect, index % pointers_per_block);
+    }
+    index -= pointers_per_block * pointers_per_block;
+
+    let triple_indirect = get_i_block(ext2, inode_offset, 14)?;
+    let double_indirect = get_block_pointer(
+        ext2,
+        triple_indirect,
+        index / (pointers_per_block * pointers_per_block),
+    )?;
+    let indirect = get_block_pointer(
+        ext2,
+        double_indirect,
+        (index / pointers_per_block) % pointers_per_block,
+    )?;
+    return get_block_pointer(ext2, indirect, index % pointers_per_block);
+}
+
+fn get_i_block(ext2: &Ext2, inode_offset: u64, index: u64) -> Result<u64> {
+    let data_block_temp: &mut [u8] = &mut [0; 4];
+    ext2.device
+        .read_at(inode_offset + 40 + (index * 4), data_block_temp)?;
+
+    return check_block(ext2, LittleEndian::read_u32(data_block_temp).into());
 }
 
-fn get_data_block_offset(opened_file: &File, inode_offset: u64, block_counter: u64) -> u64 {
+fn get_block_pointer(ext2: &Ext2, block: u64, index: u64) -> Result<u64> {
+    if block == 0 {
+        //the whole indirect tree below a missing block is a hole
+        return Ok(0);
+    }
+
     let data_block_temp: &mut [u8] = &mut [0; 4];
-    utilities::seek_read(
-        opened_file,
-        inode_offset + 40 + (block_counter * 4),
+    ext2.device.read_at(
+        block * ext2.block_size as u64 + (index * 4),
         data_block_temp,
-    )
-    .unwrap();
+    )?;
 
-    return LittleEndian::read_u32(&data_block_temp).into();
+    return check_block(ext2, LittleEndian::read_u32(data_block_temp).into());
 }
 
-fn get_size(opened_file: &File, inode_offset: u64) -> u64 {
-    let size_tmp: &mut [u8] = &mut [0; 4];
-    utilities::seek_read(opened_file, inode_offset + 4, size_tmp).unwrap();
+fn check_block(ext2: &Ext2, block: u64) -> Result<u64> {
+    //a pointer past the end of the volume means the inode or indirect block is garbage
+    if block >= ext2.num_blocks as u64 {
+        return Err(Error::CorruptStructure(format!(
+            "block pointer {} is outside the volume",
+            block
+        )));
+    }
 
-    return LittleEndian::read_u32(&size_tmp).into();
+    return Ok(block);
 }
 
-fn get_data_blocks(ext2: &Ext2, opened_file: &File, inode_offset: u64) -> u64 {
+fn get_size(ext2: &Ext2, inode_offset: u64) -> Result<u64> {
     let size_tmp: &mut [u8] = &mut [0; 4];
-    utilities::seek_read(opened_file, inode_offset + 28, size_tmp).unwrap();
-
-    return LittleEndian::read_u32(&size_tmp) as u64 / (2 << ext2.s_log_block_size);
-}
-
-fn fill_dir_entry(opened_file: &File, data_offset: u64, bytes_read: u64, dir_entry: &mut DirEntry) {
-    utilities::seek_read(
-        opened_file,
-        data_offset + 7 + (bytes_read),
-        &mut dir_entry.file_type,
-    )
-    .unwrap();
-
-    utilities::seek_read(
-        opened_file,
-        data_offset + (bytes_read),
-        &mut dir_entry.inode,
-    )
-    .unwrap();
-
-    utilities::seek_read(
-        opened_file,
-        data_offset + 4 + (bytes_read),
-        &mut dir_entry.rec_len,
-    )
-    .unwrap();
-    utilities::seek_read(
-        opened_file,
-        data_offset + 6 + (bytes_read),
-        &mut dir_entry.name_len,
-    )
-    .unwrap();
+    ext2.device.read_at(inode_offset + 4, size_tmp)?;
+    let size: u64 = LittleEndian::read_u32(size_tmp).into();
+
+    //i_size_high (i_dir_acl on directories) holds the upper half of the size of large files
+    if get_u16(ext2, inode_offset)? & 0xF000 == 0x8000 {
+        return Ok(size | (get_u32(ext2, inode_offset + 108)? as u64) << 32);
+    }
+
+    return Ok(size);
+}
+
+fn fill_dir_entry(
+    ext2: &Ext2,
+    data_offset: u64,
+    bytes_read: u64,
+    dir_entry: &mut DirEntry,
+) -> Result<()> {
+    ext2.device
+        .read_at(data_offset + 7 + (bytes_read), &mut dir_entry.file_type)?;
+
+    ext2.device
+        .read_at(data_offset + (bytes_read), &mut dir_entry.inode)?;
+
+    ext2.device
+        .read_at(data_offset + 4 + (bytes_read), &mut dir_entry.rec_len)?;
+    ext2.device
+        .read_at(data_offset + 6 + (bytes_read), &mut dir_entry.name_len)?;
 
     //make a buffer of size of the name length
     dir_entry.name = vec![0; dir_entry.name_len[0].into()];
 
-    utilities::seek_read(
-        opened_file,
-        data_offset + 8 + (bytes_read),
-        &mut dir_entry.name,
-    )
-    .unwrap();
+    ext2.device
+        .read_at(data_offset + 8 + (bytes_read), &mut dir_entry.name)?;
+
+    return Ok(());
+}
+
+fn unlink_inode(
+    ext2: &mut Ext2,
+    inode: u32,
+    blocks: &[u64],
+    file_acl: u64,
+    scrub_flag: bool,
+) -> Result<()> {
+    let offset_inode = get_inode_offset(ext2, inode)?;
+
+    //the inode is only released when the last hard link to it is gone
+    let links_count = get_u16(ext2, offset_inode + 26)?.saturating_sub(1);
+    ext2.device
+        .write_at(offset_inode + 26, &links_count.to_le_bytes())?;
+
+    if links_count > 0 {
+        return Ok(());
+    }
+
+    for block in blocks {
+        if scrub_flag {
+            ext2.device.write_at(
+                block * ext2.block_size as u64,
+                &vec![0; ext2.block_size as usize],
+            )?;
+        }
+        free_block(ext2, *block)?;
+    }
+
+    //i_file_acl, the extended attributes are in a block of their own
+    if file_acl != 0 {
+        release_xattr_block(ext2, file_acl, scrub_flag)?;
+        ext2.device.write_at(offset_inode + 104, &[0; 4])?;
+    }
+    free_inode(ext2, inode)?;
+
+    //i_dtime marks the inode as deleted, the size, i_blocks and block pointers go with it
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or(0);
+    ext2.device
+        .write_at(offset_inode + 20, &now.to_le_bytes())?;
+    ext2.device.write_at(offset_inode + 4, &[0; 4])?;
+    ext2.device.write_at(offset_inode + 28, &[0; 4])?;
+    ext2.device.write_at(offset_inode + 40, &[0; 60])?;
+    if get_u16(ext2, offset_inode)? & 0xF000 == 0x8000 {
+        ext2.device.write_at(offset_inode + 108, &[0; 4])?;
+    }
+
+    return Ok(());
+}
+
+fn release_xattr_block(ext2: &mut Ext2, block: u64, scrub_flag: bool) -> Result<()> {
+    let offset_block = block * ext2.block_size as u64;
+
+    //inodes with the same attributes share the block, h_refcount says how many there are
+    let refcount = get_u32(ext2, offset_block + 4)?;
+    if refcount > 1 {
+        ext2.device
+            .write_at(offset_block + 4, &(refcount - 1).to_le_bytes())?;
+        return Ok(());
+    }
+
+    if scrub_flag {
+        ext2.device
+            .write_at(offset_block, &vec![0; ext2.block_size as usize])?;
+    }
+    return free_block(ext2, block);
+}
+
+fn get_data_sectors(ext2: &Ext2, inode_offset: u64) -> Result<u32> {
+    //i_blocks counts the extended attribute block too, in 512 byte sectors
+    let acl_sectors = match get_u32(ext2, inode_offset + 104)? {
+        0 => 0,
+        _ => ext2.block_size / 512,
+    };
+
+    return Ok(get_u32(ext2, inode_offset + 28)?.saturating_sub(acl_sectors));
+}
+
+fn is_fast_symlink(ext2: &Ext2, inode_offset: u64) -> Result<bool> {
+    //the target is stored where the block pointers would be. Like e2fsprogs, a symlink is
+    //told to be fast by owning no data blocks, whether or not it has an attribute block
+    return Ok(get_u16(ext2, inode_offset)? & 0xF000 == 0xA000
+        && get_data_sectors(ext2, inode_offset)? == 0);
+}
+
+//the data and indirect blocks of an inode, then its extended attribute block, all of them
+//inside the volume
+fn get_owned_blocks(ext2: &Ext2, offset_inode: u64) -> Result<(Vec<u64>, u64)> {
+    //fast symlinks keep their target in i_block and device files their number, only an inode
+    //with data sectors has block pointers there
+    let mut blocks: Vec<u64> = Vec::new();
+    if get_data_sectors(ext2, offset_inode)? > 0 {
+        for index in 0..15 {
+            let depth = if index < 12 { 0 } else { index - 11 };
+            get_block_tree(
+                ext2,
+                get_i_block(ext2, offset_inode, index)?,
+                depth,
+                &mut blocks,
+            )?;
+        }
+    }
+
+    let file_acl = check_block(ext2, get_u32(ext2, offset_inode + 104)? as u64)?;
+    if file_acl != 0 && get_u32(ext2, file_acl * ext2.block_size as u64)? != XATTR_MAGIC {
+        return Err(Error::CorruptStructure(format!(
+            "block {} is not an extended attribute block",
+            file_acl
+        )));
+    }
+
+    return Ok((blocks, file_acl));
+}
+
+fn get_block_tree(ext2: &Ext2, block: u64, depth: u64, blocks: &mut Vec<u64>) -> Result<()> {
+    if block == 0 {
+        return Ok(());
+    }
+
+    //indirect blocks belong to the file just like the data blocks they point to
+    blocks.push(block);
+    if depth > 0 {
+        for index in 0..ext2.block_size as u64 / 4 {
+            let child = get_block_pointer(ext2, block, index)?;
+            get_block_tree(ext2, child, depth - 1, blocks)?;
+        }
+    }
+
+    return Ok(());
 }
 
-fn get_inode_offset(ext2: &Ext2, opened_file: &File, inode: u32) -> u64 {
-    //First, use the root inode (first inode) and convert it to inode index
+fn get_block_list(
+    ext2: &Ext2,
+    block: u64,
+    depth: u64,
+    logical: &mut u64,
+    block_list: &mut Vec<BlockRef>,
+) -> Result<()> {
+    let pointers_per_block = ext2.block_size as u64 / 4;
+    if block == 0 {
+        //a hole still takes up its place in the file
+        *logical += pointers_per_block.pow(depth as u32);
+        return Ok(());
+    }
+
+    if depth == 0 {
+        block_list.push(BlockRef::Data {
+            logical: *logical,
+            block,
+        });
+        *logical += 1;
+        return Ok(());
+    }
+
+    block_list.push(BlockRef::Indirect { depth, block });
+    for index in 0..pointers_per_block {
+        let child = get_block_pointer(ext2, block, index)?;
+        get_block_list(ext2, child, depth - 1, logical, block_list)?;
+    }
+
+    return Ok(());
+}
+
+fn free_block(ext2: &mut Ext2, block: u64) -> Result<()> {
+    let block_group = (block - ext2.first_data_block as u64) / ext2.blocks_per_group as u64;
+    let bit = (block - ext2.first_data_block as u64) % ext2.blocks_per_group as u64;
+    let offset_gd = get_group_desc_offset(ext2, block_group);
+
+    let bitmap_block = get_group_desc(ext2, block_group)?.block_bitmap as u64;
+    if clear_bitmap_bit(ext2, bitmap_block, bit)? {
+        //bg_free_blocks_count, then the count for the whole volume in the superblock
+        update_counter(ext2, offset_gd + 12, 2)?;
+        update_counter(ext2, s_free_blocks_count, 4)?;
+        let group_desc = &mut ext2.group_descs[block_group as usize];
+        group_desc.free_blocks_count = group_desc.free_blocks_count.wrapping_add(1);
+        ext2.free_blocks_count = ext2.free_blocks_count.wrapping_add(1);
+    }
+
+    return Ok(());
+}
+
+fn free_inode(ext2: &mut Ext2, inode: u32) -> Result<()> {
+    let block_group = ((inode - 1) / ext2.inodes_per_group) as u64;
+    let bit = ((inode - 1) % ext2.inodes_per_group) as u64;
+    let offset_gd = get_group_desc_offset(ext2, block_group);
+
+    let bitmap_block = get_group_desc(ext2, block_group)?.inode_bitmap as u64;
+    if clear_bitmap_bit(ext2, bitmap_block, bit)? {
+        //bg_free_inodes_count, then the count for the whole volume in the superblock
+        update_counter(ext2, offset_gd + 14, 2)?;
+        update_counter(ext2, s_free_inodes_count, 4)?;
+        let group_desc = &mut ext2.group_descs[block_group as usize];
+        group_desc.free_inodes_count = group_desc.free_inodes_count.wrapping_add(1);
+        ext2.free_inodes = ext2.free_inodes.wrapping_add(1);
+    }
+
+    return Ok(());
+}
+
+fn clear_bitmap_bit(ext2: &mut Ext2, bitmap_block: u64, bit: u64) -> Result<bool> {
+    let offset_byte = bitmap_block * ext2.block_size as u64 + bit / 8;
+    let byte: &mut [u8] = &mut [0; 1];
+    ext2.device.read_at(offset_byte, byte)?;
+
+    if byte[0] & (1 << (bit % 8)) == 0 {
+        //already free, the counters must not be touched twice
+        return Ok(false);
+    }
+
+    byte[0] &= !(1 << (bit % 8));
+    ext2.device.write_at(offset_byte, byte)?;
+
+    return Ok(true);
+}
+
+fn update_counter(ext2: &mut Ext2, offset: u64, size: usize) -> Result<()> {
+    //free counters go up by one for every block or inode released
+    if size == 2 {
+        let counter = get_u16(ext2, offset)?.wrapping_add(1);
+        ext2.device.write_at(offset, &counter.to_le_bytes())?;
+    } else {
+        let counter = get_u32(ext2, offset)?.wrapping_add(1);
+        ext2.device.write_at(offset, &counter.to_le_bytes())?;
+    }
+
+    return Ok(());
+}
+
+fn get_u16(ext2: &Ext2, offset: u64) -> Result<u16> {
+    let value_temp: &mut [u8] = &mut [0; 2];
+    ext2.device.read_at(offset, value_temp)?;
+
+    return Ok(LittleEndian::read_u16(value_temp));
+}
+
+fn get_u32(ext2: &Ext2, offset: u64) -> Result<u32> {
+    let value_temp: &mut [u8] = &mut [0; 4];
+    ext2.device.read_at(offset, value_temp)?;
+
+    return Ok(LittleEndian::read_u32(value_temp));
+}
+
+fn get_group_desc_offset(ext2: &Ext2, block_group: u64) -> u64 {
+    //the descriptor table starts in the block after the superblock, each descriptor is 32 bytes
+    return (ext2.first_data_block as u64 + 1) * ext2.block_size as u64 + block_group * 32;
+}
+
+fn get_group_desc<'a>(ext2: &'a Ext2, block_group: u64) -> Result<&'a GroupDesc> {
+    return ext2.group_descs.get(block_group as usize).ok_or_else(|| {
+        Error::CorruptStructure(format!("block group {} does not exist", block_group))
+    });
+}
+
+fn get_inode_offset(ext2: &Ext2, inode: u32) -> Result<u64> {
+    //inodes are numbered from 1, inode 0 means "no inode"
+    if inode == 0 || inode > ext2.num_inodes {
+        return Err(Error::CorruptStructure(format!(
+            "inode {} is outside the inode tables",
+            inode
+        )));
+    }
+
+    //First, convert the inode number to its index inside its block group
     let local_inode_index = (inode - 1) % ext2.inodes_per_group;
-    //println!("local inode index: {}\n", local_inode_index);
     //Second, get what block group it is in (will always be 0 for the root inode)
     let block_group = (inode - 1) / ext2.inodes_per_group;
-    //println!("BG: {}\n", block_group);
-
-    //Third, get offset of the inode table this inode is in
-    let offset_bg: u64 =
-        ((block_group * ext2.block_size * ext2.blocks_per_group) + (2048 + 8)).into();
-    //println!("offset_bg: {}\n", offset_bg);
-
-    //Fourth, go to this @ and read 4 bytes to get the @ of the inode table for this BG
-    let inode_table_temp: &mut [u8] = &mut [0; 4];
-    utilities::seek_read(opened_file, offset_bg, inode_table_temp).unwrap();
-    let inode_table_block =
-        LittleEndian::read_u32(&inode_table_temp) + block_group * ext2.blocks_per_group;
-    //println!("inode table block: {}\n", inode_table_block);
-
-    //Fifth, jump to the inode table and inode we were looking for and get the first i_block offset
-    let offset_inode: u64 = ((inode_table_block * ext2.block_size)
-        + (ext2.inode_size as u32 * local_inode_index))
-        .into();
-
-    return offset_inode;
+
+    //Third, get the descriptor of the group this inode is in, it has the @ of its inode table
+    let inode_table_block = get_group_desc(ext2, block_group as u64)?.inode_table as u64;
+
+    //Fourth, jump to the inode table and the inode we were looking for
+    let offset_inode: u64 = (inode_table_block * ext2.block_size as u64)
+        + (ext2.inode_size as u64 * local_inode_index as u64);
+
+    return Ok(offset_inode);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::Pattern;
+
+    //a single group volume of 128 blocks of 1024 bytes: the superblock is in block 1, the
+    //group descriptors in block 2, the block and inode bitmaps in blocks 3 and 4 and the 16
+    //inodes in blocks 5 and 6. Blocks 1 to 64 and every inode are in use, and the root
+    //directory is in block 10
+    const BLOCK_SIZE: usize = 1024;
+    const GROUP_DESC: usize = 2 * BLOCK_SIZE;
+    const BLOCK_BITMAP: usize = 3;
+    const INODE_BITMAP: usize = 4;
+    const INODE_TABLE: usize = 5;
+    const ROOT_DIR: usize = 10;
+
+    fn ext2_image() -> Vec<u8> {
+        let mut image = vec![0; 128 * BLOCK_SIZE];
+        LittleEndian::write_u32(&mut image[s_inodes_count as usize..], 16);
+        LittleEndian::write_u32(&mut image[s_blocks_count as usize..], 128);
+        LittleEndian::write_u32(&mut image[s_free_blocks_count as usize..], 63);
+        LittleEndian::write_u32(&mut image[s_first_data_block as usize..], 1);
+        LittleEndian::write_u32(&mut image[s_blocks_per_group as usize..], 8192);
+        LittleEndian::write_u32(&mut image[s_inodes_per_group as usize..], 16);
+        LittleEndian::write_u32(&mut image[s_rev_level as usize..], 1);
+        LittleEndian::write_u16(&mut image[s_inode_size as usize..], 128);
+        LittleEndian::write_u32(&mut image[s_first_ino as usize..], 11);
+        LittleEndian::write_u32(&mut image[s_feature_incompat as usize..], INCOMPAT_FILETYPE);
+
+        LittleEndian::write_u32(&mut image[GROUP_DESC..], BLOCK_BITMAP as u32);
+        LittleEndian::write_u32(&mut image[GROUP_DESC + 4..], INODE_BITMAP as u32);
+        LittleEndian::write_u32(&mut image[GROUP_DESC + 8..], INODE_TABLE as u32);
+        LittleEndian::write_u16(&mut image[GROUP_DESC + 12..], 63);
+        image[BLOCK_BITMAP * BLOCK_SIZE..BLOCK_BITMAP * BLOCK_SIZE + 8].fill(0xFF);
+        image[INODE_BITMAP * BLOCK_SIZE..INODE_BITMAP * BLOCK_SIZE + 2].fill(0xFF);
+
+        let root = inode_offset(2);
+        LittleEndian::write_u16(&mut image[root..], 0x41ED);
+        LittleEndian::write_u32(&mut image[root + 4..], BLOCK_SIZE as u32);
+        LittleEndian::write_u16(&mut image[root + 26..], 2);
+        LittleEndian::write_u32(&mut image[root + 28..], 2);
+        set_i_block(&mut image, 2, 0, ROOT_DIR as u32);
+        set_dir(&mut image, ROOT_DIR, &[]);
+        return image;
+    }
+
+    //the entries of a directory block after . and .., as name, inode and file type
+    fn set_dir(image: &mut [u8], block: usize, entries: &[(&str, u32, u8)]) {
+        let mut offset = block * BLOCK_SIZE;
+        let end = offset + BLOCK_SIZE;
+        let entries = [(".", 2, 2), ("..", 2, 2)].iter().chain(entries);
+        let mut entries = entries.peekable();
+        while let Some((name, inode, file_type)) = entries.next() {
+            //the last entry takes the rest of the block
+            let rec_len = match entries.peek() {
+                Some(_) => 8 + name.len().div_ceil(4) * 4,
+                None => end - offset,
+            };
+            LittleEndian::write_u32(&mut image[offset..], *inode);
+            LittleEndian::write_u16(&mut image[offset + 4..], rec_len as u16);
+            image[offset + 6] = name.len() as u8;
+            image[offset + 7] = *file_type;
+            image[offset + 8..offset + 8 + name.len()].copy_from_slice(name.as_bytes());
+            offset += rec_len;
+        }
+    }
+
+    fn inode_offset(inode: usize) -> usize {
+        return INODE_TABLE * BLOCK_SIZE + (inode - 1) * 128;
+    }
+
+    //a regular file of `size` bytes, with no blocks yet
+    fn set_file(image: &mut [u8], inode: usize, size: u32) {
+        let offset = inode_offset(inode);
+        LittleEndian::write_u16(&mut image[offset..], 0x81A4);
+        LittleEndian::write_u32(&mut image[offset + 4..], size);
+        LittleEndian::write_u16(&mut image[offset + 26..], 1);
+    }
+
+    fn set_inode_u32(image: &mut [u8], inode: usize, offset: usize, value: u32) {
+        LittleEndian::write_u32(&mut image[inode_offset(inode) + offset..], value);
+    }
+
+    fn is_used(image: &[u8], bitmap: usize, bit: usize) -> bool {
+        return image[bitmap * BLOCK_SIZE + bit / 8] & (1 << (bit % 8)) != 0;
+    }
+
+    fn read_image(ext2: &Ext2) -> Vec<u8> {
+        let mut image = vec![0; 128 * BLOCK_SIZE];
+        ext2.device.read_at(0, &mut image).unwrap();
+        return image;
+    }
+
+    fn set_i_block(image: &mut [u8], inode: usize, index: usize, block: u32) {
+        LittleEndian::write_u32(&mut image[inode_offset(inode) + 40 + index * 4..], block);
+    }
+
+    fn set_pointer(image: &mut [u8], block: usize, index: usize, target: u32) {
+        LittleEndian::write_u32(&mut image[block * BLOCK_SIZE + index * 4..], target);
+    }
+
+    fn block(image: &[u8], block: usize) -> &[u8] {
+        return &image[block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE];
+    }
+
+    //deletes `names` from the root directory one after the other and returns the image
+    fn delete(image: Vec<u8>, names: &[&str], scrub_flag: bool) -> Vec<u8> {
+        let mut ext2 = Ext2::new(image).unwrap();
+        for name in names {
+            ext2.remove(&format!("/{}", name), scrub_flag).unwrap();
+        }
+        return read_image(&ext2);
+    }
+
+    //whether the root directory of `image` still has an entry called `name`
+    fn is_found(image: &[u8], name: &str) -> bool {
+        let ext2 = Ext2::new(image).unwrap();
+        return !ext2.find(&Pattern::new(name).unwrap()).unwrap().is_empty();
+    }
+
+    #[test]
+    fn data_blocks_across_the_indirect_boundaries() {
+        //with 1024 byte blocks an indirect block holds 256 pointers, so the indirect blocks
+        //start at 12, the double indirect ones at 12 + 256 and the triple at 268 + 256 * 256
+        let mut image = ext2_image();
+        set_file(&mut image, 12, 0);
+        for index in 0..12 {
+            set_i_block(&mut image, 12, index, 20 + index as u32);
+        }
+
+        set_i_block(&mut image, 12, 12, 40);
+        set_pointer(&mut image, 40, 0, 41);
+        set_pointer(&mut image, 40, 255, 42);
+
+        set_i_block(&mut image, 12, 13, 43);
+        set_pointer(&mut image, 43, 0, 44);
+        set_pointer(&mut image, 44, 0, 45);
+        set_pointer(&mut image, 43, 255, 46);
+        set_pointer(&mut image, 46, 255, 47);
+
+        set_i_block(&mut image, 12, 14, 48);
+        set_pointer(&mut image, 48, 0, 49);
+        set_pointer(&mut image, 49, 0, 50);
+        set_pointer(&mut image, 50, 0, 51);
+        set_pointer(&mut image, 49, 1, 52);
+        set_pointer(&mut image, 52, 0, 53);
+        set_pointer(&mut image, 48, 1, 54);
+        set_pointer(&mut image, 54, 0, 55);
+        set_pointer(&mut image, 55, 0, 56);
+
+        let ext2 = Ext2::new(image).unwrap();
+        let inode_offset = get_inode_offset(&ext2, 12).unwrap();
+        let data_block =
+            |block_counter| get_data_block(&ext2, inode_offset, block_counter).unwrap();
+
+        assert_eq!(data_block(0), 20);
+        assert_eq!(data_block(11), 31);
+        assert_eq!(data_block(12), 41);
+        assert_eq!(data_block(13), 0);
+        assert_eq!(data_block(267), 42);
+        assert_eq!(data_block(268), 45);
+        assert_eq!(data_block(269), 0);
+        assert_eq!(data_block(268 + 65535), 47);
+        assert_eq!(data_block(65804), 51);
+        assert_eq!(data_block(65804 + 256), 53);
+        assert_eq!(data_block(65804 + 65536), 56);
+        assert_eq!(data_block(65804 + 2 * 65536), 0);
+    }
+
+    #[test]
+    fn block_pointers_outside_the_volume_are_corrupt() {
+        let mut image = ext2_image();
+        set_file(&mut image, 12, 0);
+        set_i_block(&mut image, 12, 12, 40);
+        set_pointer(&mut image, 40, 3, 128);
+
+        let ext2 = Ext2::new(image).unwrap();
+        let inode_offset = get_inode_offset(&ext2, 12).unwrap();
+        assert!(matches!(
+            get_data_block(&ext2, inode_offset, 15),
+            Err(Error::CorruptStructure(_))
+        ));
+    }
+
+    //a 13 block file, the last block through the indirect block 40
+    fn set_big_file(image: &mut [u8], inode: usize) {
+        set_file(image, inode, 13 * BLOCK_SIZE as u32);
+        set_inode_u32(image, inode, 28, 14 * 2);
+        for index in 0..12 {
+            set_i_block(image, inode, index, 20 + index as u32);
+        }
+        set_i_block(image, inode, 12, 40);
+        set_pointer(image, 40, 0, 41);
+        image[20 * BLOCK_SIZE..21 * BLOCK_SIZE].fill(0xAB);
+    }
+
+    //a fast symlink to /usr/share/target.txt with its attributes in block 50
+    fn set_fast_symlink(image: &mut [u8], inode: usize) {
+        let target = b"/usr/share/target.txt";
+        let offset = inode_offset(inode);
+        LittleEndian::write_u16(&mut image[offset..], 0xA1FF);
+        LittleEndian::write_u32(&mut image[offset + 4..], target.len() as u32);
+        LittleEndian::write_u16(&mut image[offset + 26..], 1);
+        //i_blocks only counts the attribute block
+        LittleEndian::write_u32(&mut image[offset + 28..], 2);
+        image[offset + 40..offset + 40 + target.len()].copy_from_slice(target);
+        set_xattr_block(image, inode, 50, 1);
+    }
+
+    fn set_xattr_block(image: &mut [u8], inode: usize, block: usize, refcount: u32) {
+        set_inode_u32(image, inode, 104, block as u32);
+        LittleEndian::write_u32(&mut image[block * BLOCK_SIZE..], XATTR_MAGIC);
+        LittleEndian::write_u32(&mut image[block * BLOCK_SIZE + 4..], refcount);
+    }
+
+    #[test]
+    fn deleting_the_last_link_releases_the_inode_and_its_blocks() {
+        let mut image = ext2_image();
+        set_big_file(&mut image, 12);
+        set_dir(&mut image, ROOT_DIR, &[("file", 12, 1), ("other", 13, 1)]);
+        set_file(&mut image, 13, 0);
+
+        let mut ext2 = Ext2::new(image).unwrap();
+        ext2.remove("/file", false).unwrap();
+        let image = read_image(&ext2);
+
+        //the 12 direct blocks, the indirect block and the block it points to
+        let released: Vec<usize> = (20..32).chain([40, 41]).collect();
+        for block in 1..=64 {
+            assert_eq!(
+                is_used(&image, BLOCK_BITMAP, block - 1),
+                !released.contains(&block)
+            );
+        }
+        assert!(!is_used(&image, INODE_BITMAP, 11));
+        assert!(is_used(&image, INODE_BITMAP, 12));
+
+        assert_eq!(LittleEndian::read_u16(&image[GROUP_DESC + 12..]), 63 + 14);
+        assert_eq!(LittleEndian::read_u16(&image[GROUP_DESC + 14..]), 1);
+        assert_eq!(
+            LittleEndian::read_u32(&image[s_free_blocks_count as usize..]),
+            63 + 14
+        );
+        assert_eq!(
+            LittleEndian::read_u32(&image[s_free_inodes_count as usize..]),
+            1
+        );
+        assert_eq!(ext2.free_blocks_count, 63 + 14);
+        assert_eq!(ext2.free_inodes, 1);
+        assert_eq!(ext2.group_descs[0].free_blocks_count, 63 + 14);
+        assert_eq!(ext2.group_descs[0].free_inodes_count, 1);
+
+        //the inode is marked deleted and the name is gone, the data is left as it was
+        let inode = inode_offset(12);
+        assert_eq!(LittleEndian::read_u16(&image[inode + 26..]), 0);
+        assert_ne!(LittleEndian::read_u32(&image[inode + 20..]), 0);
+        assert!(image[inode + 40..inode + 100].iter().all(|&b| b == 0));
+        assert!(block(&image, 20).iter().all(|&b| b == 0xAB));
+        assert!(matches!(ext2.stat("/file"), Err(Error::NotFound(_))));
+        assert!(ext2.stat("/other").is_ok());
+    }
+
+    #[test]
+    fn scrubbing_zeroes_the_released_blocks() {
+        let mut image = ext2_image();
+        set_big_file(&mut image, 12);
+        set_dir(&mut image, ROOT_DIR, &[("file", 12, 1)]);
+
+        let image = delete(image, &["file"], true);
+        for released in (20..32).chain([40, 41]) {
+            assert!(block(&image, released).iter().all(|&b| b == 0));
+        }
+    }
+
+    #[test]
+    fn other_links_keep_the_inode() {
+        let mut image = ext2_image();
+        set_big_file(&mut image, 12);
+        LittleEndian::write_u16(&mut image[inode_offset(12) + 26..], 2);
+        set_dir(&mut image, ROOT_DIR, &[("one", 12, 1), ("two", 12, 1)]);
+        let before = image.clone();
+
+        let image = delete(image, &["one"], false);
+
+        assert_eq!(LittleEndian::read_u16(&image[inode_offset(12) + 26..]), 1);
+        assert_eq!(block(&image, BLOCK_BITMAP), block(&before, BLOCK_BITMAP));
+        assert_eq!(block(&image, INODE_BITMAP), block(&before, INODE_BITMAP));
+        assert_eq!(&image[1024..2048], &before[1024..2048]);
+        assert!(!is_found(&image, "one"));
+        assert!(is_found(&image, "two"));
+    }
+
+    #[test]
+    fn fast_symlinks_with_an_attribute_block_only_release_that_block() {
+        let mut image = ext2_image();
+        set_fast_symlink(&mut image, 12);
+        set_dir(&mut image, ROOT_DIR, &[("link", 12, 7)]);
+
+        let image = delete(image, &["link"], false);
+
+        for block in 1..=64 {
+            assert_eq!(is_used(&image, BLOCK_BITMAP, block - 1), block != 50);
+        }
+        assert!(!is_used(&image, INODE_BITMAP, 11));
+        assert_eq!(
+            LittleEndian::read_u32(&image[s_free_blocks_count as usize..]),
+            63 + 1
+        );
+    }
+
+    #[test]
+    fn shared_attribute_blocks_are_released_with_their_last_user() {
+        let mut image = ext2_image();
+        set_file(&mut image, 12, 0);
+        set_file(&mut image, 13, 0);
+        set_xattr_block(&mut image, 12, 50, 2);
+        set_xattr_block(&mut image, 13, 50, 2);
+        set_dir(&mut image, ROOT_DIR, &[("a", 12, 1), ("b", 13, 1)]);
+
+        let first = delete(image.clone(), &["a"], false);
+        assert_eq!(LittleEndian::read_u32(&block(&first, 50)[4..]), 1);
+        assert!(is_used(&first, BLOCK_BITMAP, 49));
+
+        let both = delete(image, &["a", "b"], false);
+        assert!(!is_used(&both, BLOCK_BITMAP, 49));
+    }
+
+    #[test]
+    fn corrupt_inodes_are_refused_before_anything_is_written() {
+        let mut image = ext2_image();
+        set_big_file(&mut image, 12);
+        set_pointer(&mut image, 40, 3, 500);
+        set_file(&mut image, 13, 0);
+        set_xattr_block(&mut image, 13, 51, 1);
+        LittleEndian::write_u32(&mut image[51 * BLOCK_SIZE..], 0);
+        set_dir(
+            &mut image,
+            ROOT_DIR,
+            &[("pointer", 12, 1), ("xattr", 13, 1)],
+        );
+
+        let mut ext2 = Ext2::new(image.clone()).unwrap();
+        assert!(matches!(
+            ext2.remove("/pointer", false),
+            Err(Error::CorruptStructure(_))
+        ));
+        assert!(matches!(
+            ext2.remove("/xattr", false),
+            Err(Error::CorruptStructure(_))
+        ));
+        assert!(read_image(&ext2) == image);
+    }
+
+    #[test]
+    fn inodes_list_their_blocks_unless_they_are_fast_symlinks() {
+        let mut image = ext2_image();
+        set_big_file(&mut image, 12);
+        set_fast_symlink(&mut image, 13);
+        set_dir(&mut image, ROOT_DIR, &[("file", 12, 1), ("link", 13, 7)]);
+        let ext2 = Ext2::new(image).unwrap();
+
+        let file = ext2.inode("/file").unwrap();
+        assert!(!file.fast_symlink);
+        assert_eq!(file.block_list.len(), 14);
+        assert_eq!(
+            file.block_list[0],
+            BlockRef::Data {
+                logical: 0,
+                block: 20
+            }
+        );
+        assert_eq!(
+            file.block_list[12],
+            BlockRef::Indirect {
+                depth: 1,
+                block: 40
+            }
+        );
+        assert_eq!(
+            file.block_list[13],
+            BlockRef::Data {
+                logical: 12,
+                block: 41
+            }
+        );
+
+        let link = ext2.inode("/link").unwrap();
+        assert!(link.fast_symlink);
+        assert!(link.block_list.is_empty());
+        assert_eq!(link.file_acl, 50);
+    }
+
+    //the journal is inode 8, its 16 blocks are 32 to 43 and then 45 to 48 through the
+    //indirect block 44
+    const JOURNAL_BLOCKS: [usize; 16] = [
+        32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 45, 46, 47, 48,
+    ];
+
+    fn set_journal_header(image: &mut [u8], index: usize, block_type: u32, sequence: u32) {
+        let offset = JOURNAL_BLOCKS[index] * BLOCK_SIZE;
+        BigEndian::write_u32(&mut image[offset..], JBD2_MAGIC);
+        BigEndian::write_u32(&mut image[offset + 4..], block_type);
+        BigEndian::write_u32(&mut image[offset + 8..], sequence);
+    }
+
+    //a descriptor block followed by the given blocks, each with its target and tag flags
+    fn set_descriptor(image: &mut [u8], index: usize, sequence: u32, tags: &[(u32, u16, u8)]) {
+        set_journal_header(image, index, JBD2_DESCRIPTOR_BLOCK, sequence);
+        let mut offset = JOURNAL_BLOCKS[index] * BLOCK_SIZE + 12;
+        for (i, (target, flags, fill)) in tags.iter().enumerate() {
+            let flags = match i + 1 == tags.len() {
+                true => flags | JBD2_FLAG_LAST_TAG,
+                false => *flags,
+            };
+            BigEndian::write_u32(&mut image[offset..], *target);
+            BigEndian::write_u16(&mut image[offset + 6..], flags);
+            offset += 8;
+            if flags & JBD2_FLAG_SAME_UUID == 0 {
+                offset += 16;
+            }
+
+            let data = JOURNAL_BLOCKS[index + 1 + i] * BLOCK_SIZE;
+            image[data..data + BLOCK_SIZE].fill(*fill);
+            if flags & JBD2_FLAG_ESCAPE != 0 {
+                image[data..data + 4].fill(0);
+            }
+        }
+    }
+
+    fn journaled_image() -> Vec<u8> {
+        let mut image = ext2_image();
+        LittleEndian::write_u32(&mut image[s_feature_compat as usize..], COMPAT_HAS_JOURNAL);
+        LittleEndian::write_u32(
+            &mut image[s_feature_incompat as usize..],
+            INCOMPAT_FILETYPE | INCOMPAT_RECOVER,
+        );
+        LittleEndian::write_u32(&mut image[s_journal_inum as usize..], 8);
+
+        set_file(&mut image, 8, 16 * BLOCK_SIZE as u32);
+        for (index, block) in JOURNAL_BLOCKS.iter().take(12).enumerate() {
+            set_i_block(&mut image, 8, index, *block as u32);
+        }
+        set_i_block(&mut image, 8, 12, 44);
+        for (index, block) in JOURNAL_BLOCKS.iter().skip(12).enumerate() {
+            set_pointer(&mut image, 44, index, *block as u32);
+        }
+
+        let superblock = JOURNAL_BLOCKS[0] * BLOCK_SIZE;
+        BigEndian::write_u32(&mut image[superblock..], JBD2_MAGIC);
+        BigEndian::write_u32(&mut image[superblock + 4..], JBD2_SUPERBLOCK_V2);
+        BigEndian::write_u32(&mut image[superblock + 12..], BLOCK_SIZE as u32);
+        BigEndian::write_u32(&mut image[superblock + 16..], 16);
+        BigEndian::write_u32(&mut image[superblock + 20..], 1);
+        BigEndian::write_u32(&mut image[superblock + 24..], 5);
+        BigEndian::write_u32(&mut image[superblock + 28..], 1);
+
+        //what the blocks held before the transactions
+        for target in 100..104 {
+            image[target * BLOCK_SIZE..(target + 1) * BLOCK_SIZE].fill(0xEE);
+        }
+
+        //5 writes 100 and 101, which starts with the journal magic
+        set_descriptor(
+            &mut image,
+            1,
+            5,
+            &[
+                (100, 0, 0x05),
+                (101, JBD2_FLAG_SAME_UUID | JBD2_FLAG_ESCAPE, 0x15),
+            ],
+        );
+        set_journal_header(&mut image, 4, JBD2_COMMIT_BLOCK, 5);
+
+        //6 revokes 100 and 103 and writes 102, and 100 which its own revoke applies to
+        set_journal_header(&mut image, 5, JBD2_REVOKE_BLOCK, 6);
+        let revoke = JOURNAL_BLOCKS[5] * BLOCK_SIZE;
+        BigEndian::write_u32(&mut image[revoke + 12..], 16 + 2 * 4);
+        BigEndian::write_u32(&mut image[revoke + 16..], 100);
+        BigEndian::write_u32(&mut image[revoke + 20..], 103);
+        set_descriptor(
+            &mut image,
+            6,
+            6,
+            &[(102, 0, 0x06), (100, JBD2_FLAG_SAME_UUID, 0x16)],
+        );
+        set_journal_header(&mut image, 9, JBD2_COMMIT_BLOCK, 6);
+
+        //7 writes 103 again after the revoke
+        set_descriptor(&mut image, 10, 7, &[(103, 0, 0x07)]);
+        set_journal_header(&mut image, 12, JBD2_COMMIT_BLOCK, 7);
+
+        //8 was never committed
+        set_descriptor(&mut image, 13, 8, &[(102, 0, 0x08)]);
+
+        return image;
+    }
+
+    #[test]
+    fn journal_superblock() {
+        let ext2 = Ext2::new(journaled_image()).unwrap();
+        let journal = ext2.journal().unwrap();
+
+        assert_eq!(journal.version, 2);
+        assert_eq!(journal.block_size, BLOCK_SIZE as u32);
+        assert_eq!(journal.max_len, 16);
+        assert_eq!(journal.first, 1);
+        assert_eq!(journal.sequence, 5);
+        assert_eq!(journal.start, 1);
+        assert!(matches!(check_writable(&ext2), Err(Error::Unsupported(_))));
+    }
+
+    #[test]
+    fn journal_replay_skips_revoked_and_uncommitted_blocks() {
+        let mut ext2 = Ext2::new(journaled_image()).unwrap();
+        assert_eq!(ext2.replay_journal().unwrap(), 3);
+
+        let image: &mut [u8] = &mut vec![0; 128 * BLOCK_SIZE];
+        ext2.device.read_at(0, image).unwrap();
+
+        //revoked by 6, which also covers the copy in 6 itself
+        assert!(block(image, 100).iter().all(|&b| b == 0xEE));
+        //the escaped magic is put back
+        assert_eq!(BigEndian::read_u32(block(image, 101)), JBD2_MAGIC);
+        assert!(block(image, 101)[4..].iter().all(|&b| b == 0x15));
+        //8 has no commit block
+        assert!(block(image, 102).iter().all(|&b| b == 0x06));
+        //written by 7, after the revoke
+        assert!(block(image, 103).iter().all(|&b| b == 0x07));
+
+        //the journal is empty and the next transaction skips one sequence number
+        let journal = ext2.journal().unwrap();
+        assert_eq!(journal.start, 0);
+        assert_eq!(journal.sequence, 9);
+        assert_eq!(ext2.feature_incompat & INCOMPAT_RECOVER, 0);
+        assert_eq!(
+            LittleEndian::read_u32(&image[s_feature_incompat as usize..]) & INCOMPAT_RECOVER,
+            0
+        );
+        assert!(check_writable(&ext2).is_ok());
+    }
 }
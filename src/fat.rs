@@ -0,0 +1,1335 @@
+#![allow(non_upper_case_globals)]
+use crate::device::*;
+use crate::error::*;
+use crate::filesystem::*;
+use crate::utilities;
+use byteorder::{ByteOrder, LittleEndian};
+use std::fmt;
+use std::io::{self, Read};
+
+pub(crate) const BPB_BytsPerSec: u64 = 11;
+pub(crate) const BPB_SecPerClus: u64 = 13;
+pub(crate) const BPB_RsvdSecCnt: u64 = 14;
+pub(crate) const BPB_NumFATs: u64 = 16;
+pub(crate) const BPB_RootEntCnt: u64 = 17;
+pub(crate) const BPB_TotSec16: u64 = 19;
+pub(crate) const BPB_Media: u64 = 21;
+pub(crate) const BPB_FATSz16: u64 = 22;
+pub(crate) const BPB_TotSec32: u64 = 32;
+pub(crate) const BS_VolLab: u64 = 43;
+pub(crate) const BS_FilSysType: u64 = 54;
+
+//FAT32 has a longer BPB, which moves the rest of the boot sector 28 bytes further
+pub(crate) const BPB_FATSz32: u64 = 36;
+pub(crate) const BPB_ExtFlags: u64 = 40;
+pub(crate) const BPB_RootClus: u64 = 44;
+pub(crate) const BPB_FSInfo: u64 = 48;
+pub(crate) const BPB_BkBootSec: u64 = 50;
+pub(crate) const BS_VolLab32: u64 = 71;
+pub(crate) const BS_FilSysType32: u64 = 82;
+
+const FSI_LeadSig: u64 = 0;
+const FSI_StrucSig: u64 = 484;
+const FSI_Free_Count: u64 = 488;
+const FSI_Nxt_Free: u64 = 492;
+//0xFFFFFFFF in FSI_Free_Count or FSI_Nxt_Free means the value is unknown
+const FSI_Unknown: u32 = 0xFFFF_FFFF;
+
+//the FAT type is decided by the number of clusters alone, whatever the boot sector says
+const FAT12_MAX_CLUSTERS: u64 = 4084;
+const FAT16_MAX_CLUSTERS: u64 = 65524;
+
+/// The width of the entries of the FAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+/// A FAT12, FAT16 or FAT32 volume.
+pub struct Fat<'a> {
+    pub device: Box<dyn BlockDevice + 'a>,
+    pub fat_type: FatType,
+    pub volume_name: [u8; 8],
+    pub sector_size: u16,
+    pub sectors_per_cluster: u8,
+    pub num_fats: u8,
+    pub root_entries: u16,
+    pub sectors_per_fat: u32,
+    pub reserved_sectors: u16,
+    pub volume_label: [u8; 11],
+    pub total_sectors: u32,
+    //sectors left for clusters once the reserved sectors, FATs and root directory are taken out
+    pub data_sectors: u32,
+    pub cluster_count: u32,
+    //the rest only exists on FAT32
+    pub root_cluster: u32,
+    //the only FAT in use when mirroring is disabled, otherwise every FAT is kept the same
+    pub active_fat: Option<u8>,
+    pub fs_info_sector: u16,
+    pub free_clusters: Option<u32>,
+    pub next_free_cluster: Option<u32>,
+    pub backup_boot_sector: u16,
+    pub backup_boot_matches: bool,
+}
+
+/// The name `Fat` had when only FAT16 was supported.
+pub type Fat16<'a> = Fat<'a>;
+
+#[derive(Default, Clone)]
+struct DirEntry {
+    pub name: [u8; 8],
+    pub extension: [u8; 3],
+    pub filesize: [u8; 4],
+    pub file_type: [u8; 1],
+    pub starting_cluster: [u8; 2],
+    //only used by FAT32
+    pub starting_cluster_high: [u8; 2],
+    pub creation_hundredths: [u8; 1],
+    pub creation_time: [u8; 2],
+    pub creation_date: [u8; 2],
+    pub access_date: [u8; 2],
+    pub write_time: [u8; 2],
+    pub write_date: [u8; 2],
+    pub long_name: String,
+    pub offset: u64,
+    pub lfn_offsets: Vec<u64>,
+}
+
+struct FileReader<'a> {
+    fat: &'a Fat<'a>,
+    chain: Vec<u32>,
+    size: u64,
+    position: u64,
+}
+
+impl FatType {
+    pub fn from_cluster_count(cluster_count: u64) -> FatType {
+        if cluster_count <= FAT12_MAX_CLUSTERS {
+            return FatType::Fat12;
+        } else if cluster_count <= FAT16_MAX_CLUSTERS {
+            return FatType::Fat16;
+        }
+        return FatType::Fat32;
+    }
+
+    //entries from here up mark a bad cluster, and the ones after it the end of a chain
+    fn bad_cluster(&self) -> u32 {
+        match self {
+            FatType::Fat12 => return 0xFF7,
+            FatType::Fat16 => return 0xFFF7,
+            FatType::Fat32 => return 0x0FFF_FFF7,
+        }
+    }
+}
+
+impl fmt::Display for FatType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FatType::Fat12 => write!(f, "FAT12"),
+            FatType::Fat16 => write!(f, "FAT16"),
+            FatType::Fat32 => write!(f, "FAT32"),
+        }
+    }
+}
+
+impl<'a> Fat<'a> {
+    pub fn new(device: impl BlockDevice + 'a) -> Result<Fat<'a>> {
+        let mut fat = Fat {
+            device: Box::new(device),
+            fat_type: FatType::Fat16,
+            volume_name: [0; 8],
+            sector_size: 0,
+            sectors_per_cluster: 0,
+            num_fats: 0,
+            root_entries: 0,
+            sectors_per_fat: 0,
+            reserved_sectors: 0,
+            total_sectors: 0,
+            data_sectors: 0,
+            cluster_count: 0,
+            volume_label: [0; 11],
+            root_cluster: 0,
+            active_fat: None,
+            fs_info_sector: 0,
+            free_clusters: None,
+            next_free_cluster: None,
+            backup_boot_sector: 0,
+            backup_boot_matches: false,
+        };
+        fat.load_info()?;
+
+        return Ok(fat);
+    }
+
+    fn load_info(&mut self) -> Result<()> {
+        // ------------------------ VOLUME NAME ------------------------
+        self.device.read_at(3, &mut self.volume_name)?;
+
+        // ------------------------ SIZE ------------------------
+        let sector_size_temp: &mut [u8] = &mut [0; 2];
+        self.device.read_at(BPB_BytsPerSec, sector_size_temp)?;
+        self.sector_size = LittleEndian::read_u16(sector_size_temp);
+
+        // ------------------------ SECTORS PER CLUSTER ------------------------
+        let sectors_per_cluster_temp: &mut [u8] = &mut [0; 1];
+        self.device
+            .read_at(BPB_SecPerClus, sectors_per_cluster_temp)?;
+        self.sectors_per_cluster = sectors_per_cluster_temp[0];
+
+        // ------------------------ RESERVED SECTORS ------------------------
+        let reserved_sectors_temp: &mut [u8] = &mut [0; 2];
+        self.device.read_at(BPB_RsvdSecCnt, reserved_sectors_temp)?;
+        self.reserved_sectors = LittleEndian::read_u16(reserved_sectors_temp);
+
+        // ------------------------ NUM FATS ------------------------
+        let num_fats_temp: &mut [u8] = &mut [0; 1];
+        self.device.read_at(BPB_NumFATs, num_fats_temp)?;
+        self.num_fats = num_fats_temp[0];
+
+        // ------------------------ ROOT ENTRIES ------------------------
+        let root_entries_temp: &mut [u8] = &mut [0; 2];
+        self.device.read_at(BPB_RootEntCnt, root_entries_temp)?;
+        self.root_entries = LittleEndian::read_u16(root_entries_temp);
+
+        // ------------------------ Total Sectors ------------------------
+        //volumes with 65536 sectors or more leave the 16 bit count at 0 and use the 32 bit one
+        let total_sectors_temp: &mut [u8] = &mut [0; 2];
+        self.device.read_at(BPB_TotSec16, total_sectors_temp)?;
+        self.total_sectors = LittleEndian::read_u16(total_sectors_temp) as u32;
+        if self.total_sectors == 0 {
+            let total_sectors_temp: &mut [u8] = &mut [0; 4];
+            self.device.read_at(BPB_TotSec32, total_sectors_temp)?;
+            self.total_sectors = LittleEndian::read_u32(total_sectors_temp);
+        }
+
+        // ------------------------ SECOTRS PER FAT ------------------------
+        //FAT32 leaves the 16 bit count at 0 and uses the 32 bit one
+        let sectors_per_fat_temp: &mut [u8] = &mut [0; 2];
+        self.device.read_at(BPB_FATSz16, sectors_per_fat_temp)?;
+        self.sectors_per_fat = LittleEndian::read_u16(sectors_per_fat_temp) as u32;
+        if self.sectors_per_fat == 0 {
+            let sectors_per_fat_temp: &mut [u8] = &mut [0; 4];
+            self.device.read_at(BPB_FATSz32, sectors_per_fat_temp)?;
+            self.sectors_per_fat = LittleEndian::read_u32(sectors_per_fat_temp);
+        }
+
+        //all the offsets are computed from these, a zero would send us to the wrong place
+        if self.sector_size == 0 || self.sectors_per_cluster == 0 || self.num_fats == 0 {
+            return Err(Error::CorruptStructure(String::from(
+                "the boot sector has a zero sector size, cluster size or FAT count",
+            )));
+        }
+
+        // ------------------------ DATA REGION ------------------------
+        let metadata_sectors = self.reserved_sectors as u64
+            + self.num_fats as u64 * self.sectors_per_fat as u64
+            + get_root_dir_sectors(self);
+        if metadata_sectors >= self.total_sectors as u64 {
+            return Err(Error::CorruptStructure(format!(
+                "the volume has {} sectors, but its FATs and root directory end at sector {}",
+                self.total_sectors, metadata_sectors
+            )));
+        }
+        self.data_sectors = self.total_sectors - metadata_sectors as u32;
+        self.cluster_count = self.data_sectors / self.sectors_per_cluster as u32;
+
+        self.fat_type = FatType::from_cluster_count(self.cluster_count as u64);
+        if self.fat_type != FatType::Fat32 {
+            // ------------------------ VOLUME LABEL ------------------------
+            self.device.read_at(BS_VolLab, &mut self.volume_label)?;
+            return Ok(());
+        }
+
+        // ------------------------ VOLUME LABEL ------------------------
+        self.device.read_at(BS_VolLab32, &mut self.volume_label)?;
+
+        // ------------------------ ROOT CLUSTER ------------------------
+        let root_cluster_temp: &mut [u8] = &mut [0; 4];
+        self.device.read_at(BPB_RootClus, root_cluster_temp)?;
+        self.root_cluster = LittleEndian::read_u32(root_cluster_temp) & 0x0FFF_FFFF;
+        if self.root_cluster < 2 || self.root_cluster >= self.cluster_count + 2 {
+            return Err(Error::CorruptStructure(format!(
+                "the root directory starts at cluster {}, the volume has {}",
+                self.root_cluster, self.cluster_count
+            )));
+        }
+
+        // ------------------------ FAT MIRRORING ------------------------
+        //bit 7 turns mirroring off, then bits 0-3 say which FAT is the only one in use
+        let ext_flags_temp: &mut [u8] = &mut [0; 2];
+        self.device.read_at(BPB_ExtFlags, ext_flags_temp)?;
+        let ext_flags = LittleEndian::read_u16(ext_flags_temp);
+        if ext_flags & 0x80 == 0x80 {
+            let active_fat = (ext_flags & 0x0F) as u8;
+            if active_fat >= self.num_fats {
+                return Err(Error::CorruptStructure(format!(
+                    "FAT {} is the active one, the volume has {} FATs",
+                    active_fat, self.num_fats
+                )));
+            }
+            self.active_fat = Some(active_fat);
+        }
+
+        // ------------------------ FSINFO ------------------------
+        //FSInfo only holds hints, a missing or broken one is ignored
+        let fs_info_sector_temp: &mut [u8] = &mut [0; 2];
+        self.device.read_at(BPB_FSInfo, fs_info_sector_temp)?;
+        self.fs_info_sector = LittleEndian::read_u16(fs_info_sector_temp);
+        if self.fs_info_sector != 0 && self.fs_info_sector < self.reserved_sectors {
+            let fs_info: &mut [u8] = &mut [0; 512];
+            self.device.read_at(get_fs_info_offset(self), fs_info)?;
+            let lead_sig = LittleEndian::read_u32(&fs_info[FSI_LeadSig as usize..]);
+            let struc_sig = LittleEndian::read_u32(&fs_info[FSI_StrucSig as usize..]);
+            if lead_sig == 0x4161_5252 && struc_sig == 0x6141_7272 {
+                self.free_clusters =
+                    Some(LittleEndian::read_u32(&fs_info[FSI_Free_Count as usize..]))
+                        .filter(|&free| free != FSI_Unknown && free <= self.cluster_count);
+                self.next_free_cluster =
+                    Some(LittleEndian::read_u32(&fs_info[FSI_Nxt_Free as usize..]))
+                        .filter(|&next| next != FSI_Unknown && next < self.cluster_count + 2);
+            } else {
+                self.fs_info_sector = 0;
+            }
+        } else {
+            self.fs_info_sector = 0;
+        }
+
+        // ------------------------ BACKUP BOOT SECTOR ------------------------
+        let backup_boot_sector_temp: &mut [u8] = &mut [0; 2];
+        self.device
+            .read_at(BPB_BkBootSec, backup_boot_sector_temp)?;
+        self.backup_boot_sector = LittleEndian::read_u16(backup_boot_sector_temp);
+        if self.backup_boot_sector != 0 && self.backup_boot_sector < self.reserved_sectors {
+            let boot_sector: &mut [u8] = &mut [0; 512];
+            let backup_boot_sector: &mut [u8] = &mut [0; 512];
+            self.device.read_at(0, boot_sector)?;
+            self.device.read_at(
+                self.backup_boot_sector as u64 * self.sector_size as u64,
+                backup_boot_sector,
+            )?;
+            self.backup_boot_matches = boot_sector == backup_boot_sector;
+        }
+
+        return Ok(());
+    }
+}
+
+impl<'a> Filesystem for Fat<'a> {
+    fn volume_info(&self) -> VolumeInfo {
+        let fields = vec![
+            (
+                String::from("Volume Name"),
+                String::from_utf8_lossy(&self.volume_name).to_string(),
+            ),
+            (String::from("Size"), self.sector_size.to_string()),
+            (
+                String::from("Sectors per cluster"),
+                self.sectors_per_cluster.to_string(),
+            ),
+            (
+                String::from("Reserved sectors"),
+                self.reserved_sectors.to_string(),
+            ),
+            (String::from("Number of FATs"), self.num_fats.to_string()),
+            (String::from("Root entries"), self.root_entries.to_string()),
+            (
+                String::from("Sectors per FAT"),
+                self.sectors_per_fat.to_string(),
+            ),
+            (
+                String::from("Total Sectors"),
+                self.total_sectors.to_string(),
+            ),
+            (
+                String::from("Volume size"),
+                format!(
+                    "{} bytes",
+                    self.total_sectors as u64 * self.sector_size as u64
+                ),
+            ),
+            (
+                String::from("Data region"),
+                format!(
+                    "{} sectors, {} bytes",
+                    self.data_sectors,
+                    self.data_sectors as u64 * self.sector_size as u64
+                ),
+            ),
+            (String::from("Clusters"), self.cluster_count.to_string()),
+            (
+                String::from("Volume Label"),
+                String::from_utf8_lossy(&self.volume_label).to_string(),
+            ),
+        ];
+
+        let mut sections = vec![InfoSection {
+            title: String::new(),
+            fields,
+        }];
+
+        if self.fat_type == FatType::Fat32 {
+            //FSInfo values are only hints, they are shown as they are stored
+            let hint = |value: Option<u32>| match value {
+                Some(value) => value.to_string(),
+                None => String::from("unknown"),
+            };
+            sections.push(InfoSection {
+                title: String::from("FAT32"),
+                fields: vec![
+                    (String::from("Root cluster"), self.root_cluster.to_string()),
+                    (
+                        String::from("FAT mirroring"),
+                        match self.active_fat {
+                            Some(active_fat) => format!("off, only FAT {} is used", active_fat),
+                            None => String::from("on"),
+                        },
+                    ),
+                    (
+                        String::from("FSInfo sector"),
+                        match self.fs_info_sector {
+                            0 => String::from("none"),
+                            sector => sector.to_string(),
+                        },
+                    ),
+                    (String::from("Free clusters"), hint(self.free_clusters)),
+                    (
+                        String::from("Next free cluster"),
+                        hint(self.next_free_cluster),
+                    ),
+                    (
+                        String::from("Backup boot sector"),
+                        match (self.backup_boot_sector, self.backup_boot_matches) {
+                            (0, _) => String::from("none"),
+                            (sector, true) => format!("{}, same as the boot sector", sector),
+                            (sector, false) => format!("{}, differs from the boot sector", sector),
+                        },
+                    ),
+                ],
+            });
+        }
+
+        return VolumeInfo {
+            filesystem: self.fat_type.to_string(),
+            sections,
+        };
+    }
+
+    fn stat(&self, path: &str) -> Result<Metadata> {
+        return Ok(get_metadata(&lookup(self, path)?));
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<Metadata>> {
+        let dir_entry = lookup(self, path)?;
+        if (dir_entry.file_type[0] & 16) != 16 {
+            return Err(Error::Unsupported(format!(
+                "{} is not a directory, only directories can be listed",
+                path
+            )));
+        }
+
+        let dir_entries = read_dir_entries(self, get_starting_cluster(&dir_entry))?;
+
+        return Ok(dir_entries.iter().map(get_metadata).collect());
+    }
+
+    fn open(&self, path: &str) -> Result<Box<dyn Read + '_>> {
+        let dir_entry = lookup(self, path)?;
+        if (dir_entry.file_type[0] & 16) == 16 {
+            return Err(Error::Unsupported(format!(
+                "{} is a directory, only files can be read",
+                path
+            )));
+        }
+
+        //the file is stored in the clusters of its chain, the last one is only partially used
+        return Ok(Box::new(FileReader {
+            fat: self,
+            chain: get_cluster_chain(self, get_starting_cluster(&dir_entry))?,
+            size: LittleEndian::read_u32(&dir_entry.filesize) as u64,
+            position: 0,
+        }));
+    }
+
+    fn remove(&mut self, path: &str, scrub_flag: bool) -> Result<()> {
+        let dir_entry = lookup(self, path)?;
+        if (dir_entry.file_type[0] & 16) == 16 || dir_entry.offset == 0 {
+            return Err(Error::Unsupported(format!(
+                "{} is a directory, only files can be deleted",
+                path
+            )));
+        }
+
+        //a corrupt chain is found before anything is written, not after the entry is gone
+        let chain = get_cluster_chain(self, get_starting_cluster(&dir_entry))?;
+
+        // write a 0xE5 at the start of the name to mark the entry as free
+        let buf: &mut [u8] = &mut [0; 1];
+        buf[0] = 0xE5;
+        self.device.write_at(dir_entry.offset, buf)?;
+        //the long name entries of the file are freed as well
+        for lfn_offset in &dir_entry.lfn_offsets {
+            self.device.write_at(*lfn_offset, buf)?;
+        }
+        free_cluster_chain(self, &chain, scrub_flag)?;
+
+        return Ok(());
+    }
+
+    fn case_sensitive(&self) -> bool {
+        return false;
+    }
+}
+
+impl<'a> Read for FileReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let cluster_size = self.fat.sectors_per_cluster as u64 * self.fat.sector_size as u64;
+        let cluster_index = (self.position / cluster_size) as usize;
+        if cluster_index >= self.chain.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the cluster chain ended before the end of the file",
+            ));
+        }
+
+        //never read past the end of the current cluster or the end of the file
+        let offset_in_cluster = self.position % cluster_size;
+        let to_read = (buf.len() as u64)
+            .min(cluster_size - offset_in_cluster)
+            .min(self.size - self.position) as usize;
+        self.fat.device.read_at(
+            get_cluster_offset(self.fat, self.chain[cluster_index]) + offset_in_cluster,
+            &mut buf[..to_read],
+        )?;
+        self.position += to_read as u64;
+
+        return Ok(to_read);
+    }
+}
+
+fn lookup(fat: &Fat, path: &str) -> Result<DirEntry> {
+    //the root directory has no entry of its own, it is the directory at cluster 0
+    let mut dir_entry = DirEntry {
+        file_type: [16],
+        ..Default::default()
+    };
+
+    for component in utilities::split_path(path) {
+        if (dir_entry.file_type[0] & 16) != 16 {
+            return Err(Error::NotFound(path.to_string()));
+        }
+
+        //FAT names are case insensitive, and a file can be named by its long or short name
+        dir_entry = read_dir_entries(fat, get_starting_cluster(&dir_entry))?
+            .into_iter()
+            .find(|dir_entry| {
+                get_short_name(dir_entry).eq_ignore_ascii_case(component)
+                    || dir_entry.long_name.eq_ignore_ascii_case(component)
+            })
+            .ok_or_else(|| Error::NotFound(path.to_string()))?;
+    }
+
+    return Ok(dir_entry);
+}
+
+fn read_dir_entries(fat: &Fat, starting_cluster: u32) -> Result<Vec<DirEntry>> {
+    let mut dir_entries: Vec<DirEntry> = Vec::new();
+    let mut dir_entry: DirEntry = DirEntry::default();
+
+    //long name entries come right before the short entry they belong to, last part first
+    let mut lfn_name: Vec<u16> = Vec::new();
+    let mut lfn_offsets: Vec<u64> = Vec::new();
+    let mut lfn_checksum = 0;
+    let mut lfn_next_ordinal = 0;
+
+    for offset_dir in get_dir_entry_offsets(fat, starting_cluster)? {
+        //first 8 bytes is the name
+        fat.device.read_at(offset_dir, &mut dir_entry.name)?;
+
+        //read the file type
+        fat.device
+            .read_at(offset_dir + 11, &mut dir_entry.file_type)?;
+
+        if dir_entry.name[0] == 0 {
+            //no more entries in this directory
+            break;
+        } else if dir_entry.file_type[0] == 15 && dir_entry.name[0] != 0xE5 {
+            let lfn_entry: &mut [u8] = &mut [0; 32];
+            fat.device.read_at(offset_dir, lfn_entry)?;
+
+            let ordinal = lfn_entry[0] & 0x1F;
+            if lfn_entry[0] & 0x40 == 0x40 {
+                //the last part of the name is stored first, it starts a new sequence
+                lfn_name.clear();
+                lfn_offsets.clear();
+                lfn_checksum = lfn_entry[13];
+            } else if ordinal != lfn_next_ordinal || lfn_entry[13] != lfn_checksum {
+                //out of order or from another file, this sequence is orphaned
+                lfn_name.clear();
+                lfn_offsets.clear();
+                lfn_next_ordinal = 0;
+                continue;
+            }
+
+            lfn_name.splice(0..0, get_lfn_part(lfn_entry));
+            lfn_offsets.push(offset_dir);
+            lfn_next_ordinal = ordinal.wrapping_sub(1);
+            continue;
+        } else if dir_entry.file_type[0] == 15
+            || dir_entry.file_type[0] == 8
+            || dir_entry.name[0] == 0xE5
+        {
+            lfn_name.clear();
+            lfn_offsets.clear();
+            continue;
+        }
+
+        // next 3 bytes is the extension
+        fat.device
+            .read_at(offset_dir + 8, &mut dir_entry.extension)?;
+
+        //the long name is only valid if the whole sequence was read and it belongs to this entry
+        dir_entry.long_name = String::new();
+        if !lfn_name.is_empty()
+            && lfn_next_ordinal == 0
+            && lfn_checksum == get_short_name_checksum(&dir_entry.name, &dir_entry.extension)
+        {
+            let name_end = lfn_name
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(lfn_name.len());
+            dir_entry.long_name = String::from_utf16_lossy(&lfn_name[..name_end]);
+        } else {
+            lfn_offsets.clear();
+        }
+        lfn_name.clear();
+
+        let name = get_short_name(&dir_entry);
+
+        if name.eq_ignore_ascii_case(".") || name.eq_ignore_ascii_case("..") {
+            continue;
+        }
+
+        // creation time (with its hundredths of a second) and date, and last access date
+        fat.device
+            .read_at(offset_dir + 13, &mut dir_entry.creation_hundredths)?;
+        fat.device
+            .read_at(offset_dir + 14, &mut dir_entry.creation_time)?;
+        fat.device
+            .read_at(offset_dir + 16, &mut dir_entry.creation_date)?;
+        fat.device
+            .read_at(offset_dir + 18, &mut dir_entry.access_date)?;
+
+        // last modification time and date
+        fat.device
+            .read_at(offset_dir + 22, &mut dir_entry.write_time)?;
+        fat.device
+            .read_at(offset_dir + 24, &mut dir_entry.write_date)?;
+
+        // Finally, read the starting cluster from dir entry
+        fat.device
+            .read_at(offset_dir + 26, &mut dir_entry.starting_cluster)?;
+        if fat.fat_type == FatType::Fat32 {
+            fat.device
+                .read_at(offset_dir + 20, &mut dir_entry.starting_cluster_high)?;
+        }
+
+        // last 4 bytes is size (32 -4 is starting offset)
+        fat.device
+            .read_at(offset_dir + 28, &mut dir_entry.filesize)?;
+        dir_entry.offset = offset_dir;
+        dir_entry.lfn_offsets = lfn_offsets.clone();
+
+        dir_entries.push(dir_entry.clone());
+    }
+
+    return Ok(dir_entries);
+}
+
+fn get_metadata(dir_entry: &DirEntry) -> Metadata {
+    let file_type = if (dir_entry.file_type[0] & 16) == 16 {
+        FileType::Directory
+    } else {
+        FileType::File
+    };
+
+    return Metadata {
+        name: get_name(dir_entry),
+        short_name: Some(get_short_name(dir_entry)),
+        file_type,
+        size: LittleEndian::read_u32(&dir_entry.filesize) as u64,
+        id: get_starting_cluster(dir_entry) as u64,
+        modified: utilities::convert_dos_date_time(dir_entry.write_date, dir_entry.write_time, 0),
+        details: vec![
+            (
+                String::from("Attributes"),
+                get_attributes(dir_entry.file_type[0]),
+            ),
+            (
+                String::from("Cluster"),
+                get_starting_cluster(dir_entry).to_string(),
+            ),
+            (
+                String::from("Created"),
+                match utilities::convert_dos_date_time(
+                    dir_entry.creation_date,
+                    dir_entry.creation_time,
+                    dir_entry.creation_hundredths[0],
+                ) {
+                    Some(created) => created.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+                    None => String::from("-"),
+                },
+            ),
+            (
+                String::from("Accessed"),
+                match utilities::convert_dos_date(dir_entry.access_date) {
+                    Some(accessed) => accessed.format("%Y-%m-%d").to_string(),
+                    None => String::from("-"),
+                },
+            ),
+        ],
+    };
+}
+
+fn get_starting_cluster(dir_entry: &DirEntry) -> u32 {
+    //the high half is only read on FAT32, on the others it stays at 0
+    return (LittleEndian::read_u16(&dir_entry.starting_cluster_high) as u32) << 16
+        | LittleEndian::read_u16(&dir_entry.starting_cluster) as u32;
+}
+
+fn get_attributes(attributes: u8) -> String {
+    //read-only, hidden, system and archive, in the order DOS shows them
+    let mut flags = String::new();
+    for (bit, flag) in [(0x01, 'R'), (0x02, 'H'), (0x04, 'S'), (0x20, 'A')] {
+        flags.push(if attributes & bit == bit { flag } else { '-' });
+    }
+
+    return flags;
+}
+
+fn get_name(dir_entry: &DirEntry) -> String {
+    //the long name is the real name of the file, the short one is only there for compatibility
+    if dir_entry.long_name.is_empty() {
+        return get_short_name(dir_entry);
+    }
+
+    return dir_entry.long_name.clone();
+}
+
+fn get_short_name(dir_entry: &DirEntry) -> String {
+    //short names use the OEM code page, anything outside ASCII is shown as a replacement character
+    let mut name = utilities::remove_whitespace(&String::from_utf8_lossy(&dir_entry.name));
+
+    let extension = utilities::remove_whitespace(&String::from_utf8_lossy(&dir_entry.extension));
+
+    if extension.capacity() > 0 {
+        name.push('.');
+        name.push_str(&extension);
+    }
+
+    return name;
+}
+
+fn free_cluster_chain(fat: &mut Fat, chain: &[u32], scrub_flag: bool) -> Result<()> {
+    let cluster_size = fat.sectors_per_cluster as u64 * fat.sector_size as u64;
+
+    for cluster in chain {
+        set_fat_entry(fat, *cluster, 0)?;
+
+        if scrub_flag {
+            fat.device.write_at(
+                get_cluster_offset(fat, *cluster),
+                &vec![0; cluster_size as usize],
+            )?;
+        }
+    }
+
+    //keep the FSInfo hints right, so the next allocation doesn't need a full FAT scan
+    if fat.fs_info_sector != 0 && !chain.is_empty() {
+        if let Some(free_clusters) = fat.free_clusters {
+            let free_clusters = (free_clusters + chain.len() as u32).min(fat.cluster_count);
+            fat.device.write_at(
+                get_fs_info_offset(fat) + FSI_Free_Count,
+                &free_clusters.to_le_bytes(),
+            )?;
+            fat.free_clusters = Some(free_clusters);
+        }
+
+        let first_freed = *chain.iter().min().unwrap_or(&0);
+        if let Some(next_free_cluster) = fat.next_free_cluster {
+            if first_freed < next_free_cluster {
+                fat.device.write_at(
+                    get_fs_info_offset(fat) + FSI_Nxt_Free,
+                    &first_freed.to_le_bytes(),
+                )?;
+                fat.next_free_cluster = Some(first_freed);
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+fn get_lfn_part(lfn_entry: &[u8]) -> Vec<u16> {
+    //each long name entry holds 13 UTF-16 characters split in 3 chunks (5 + 6 + 2)
+    let mut part: Vec<u16> = Vec::new();
+    for range in [1..11, 14..26, 28..32] {
+        for c in lfn_entry[range].chunks(2) {
+            part.push(LittleEndian::read_u16(c));
+        }
+    }
+
+    //names that don't fill the last entry are terminated by 0x0000 and padded with 0xFFFF
+    part.retain(|&c| c != 0xFFFF);
+    return part;
+}
+
+fn get_short_name_checksum(name: &[u8; 8], extension: &[u8; 3]) -> u8 {
+    let mut checksum: u8 = 0;
+    for c in name.iter().chain(extension.iter()) {
+        checksum = ((checksum & 1) << 7)
+            .wrapping_add(checksum >> 1)
+            .wrapping_add(*c);
+    }
+
+    return checksum;
+}
+
+fn get_fat_offset(fat: &Fat) -> u64 {
+    //the FATs come right after the reserved sectors
+    return fat.reserved_sectors as u64 * fat.sector_size as u64;
+}
+
+fn get_fat_size(fat: &Fat) -> u64 {
+    return fat.sectors_per_fat as u64 * fat.sector_size as u64;
+}
+
+fn get_fs_info_offset(fat: &Fat) -> u64 {
+    return fat.fs_info_sector as u64 * fat.sector_size as u64;
+}
+
+fn get_root_dir_offset(fat: &Fat) -> u64 {
+    //FAT32 has no root directory region, its size is 0 and the data region starts here
+    return get_fat_offset(fat) + fat.num_fats as u64 * get_fat_size(fat);
+}
+
+fn get_root_dir_sectors(fat: &Fat) -> u64 {
+    //the root directory always takes whole sectors, even if the last one is not full
+    return (fat.root_entries as u64 * 32).div_ceil(fat.sector_size as u64);
+}
+
+fn get_cluster_offset(fat: &Fat, cluster: u32) -> u64 {
+    //the data region starts after the root directory, and its first cluster is cluster 2
+    let data_region_offset =
+        get_root_dir_offset(fat) + get_root_dir_sectors(fat) * fat.sector_size as u64;
+    let cluster_size = fat.sectors_per_cluster as u64 * fat.sector_size as u64;
+
+    return data_region_offset + (cluster as u64 - 2) * cluster_size;
+}
+
+fn get_cluster_chain(fat: &Fat, starting_cluster: u32) -> Result<Vec<u32>> {
+    let mut chain: Vec<u32> = Vec::new();
+    let bad_cluster = fat.fat_type.bad_cluster();
+
+    let mut cluster = starting_cluster;
+    //0 and 1 are reserved, and every value from the bad cluster marker up ends the chain
+    //the length check stops us from looping forever on a corrupt (circular) chain
+    while (2..bad_cluster).contains(&cluster) && chain.len() <= fat.cluster_count as usize {
+        //a cluster past the last one would be read from outside the data region
+        if cluster >= fat.cluster_count + 2 {
+            return Err(Error::CorruptStructure(format!(
+                "the cluster chain starting at {} points to cluster {}, the volume has {}",
+                starting_cluster, cluster, fat.cluster_count
+            )));
+        }
+        chain.push(cluster);
+        cluster = get_fat_entry(fat, cluster)?;
+    }
+
+    if cluster == bad_cluster {
+        return Err(Error::CorruptStructure(format!(
+            "the cluster chain starting at {} reaches a bad cluster",
+            starting_cluster
+        )));
+    }
+
+    return Ok(chain);
+}
+
+fn get_fat_entry(fat: &Fat, cluster: u32) -> Result<u32> {
+    //every entry holds the number of the next cluster of the chain
+    let fat_offset = get_fat_offset(fat) + fat.active_fat.unwrap_or(0) as u64 * get_fat_size(fat);
+    match fat.fat_type {
+        FatType::Fat12 => {
+            //two 12 bit entries are packed in 3 bytes, odd clusters use the high 12 bits
+            let fat_entry_temp: &mut [u8] = &mut [0; 2];
+            fat.device
+                .read_at(fat_offset + cluster as u64 * 3 / 2, fat_entry_temp)?;
+            let entry = LittleEndian::read_u16(fat_entry_temp) as u32;
+            if cluster % 2 == 1 {
+                return Ok(entry >> 4);
+            }
+            return Ok(entry & 0x0FFF);
+        }
+        FatType::Fat16 => {
+            let fat_entry_temp: &mut [u8] = &mut [0; 2];
+            fat.device
+                .read_at(fat_offset + cluster as u64 * 2, fat_entry_temp)?;
+            return Ok(LittleEndian::read_u16(fat_entry_temp) as u32);
+        }
+        FatType::Fat32 => {
+            //the top 4 bits are reserved
+            let fat_entry_temp: &mut [u8] = &mut [0; 4];
+            fat.device
+                .read_at(fat_offset + cluster as u64 * 4, fat_entry_temp)?;
+            return Ok(LittleEndian::read_u32(fat_entry_temp) & 0x0FFF_FFFF);
+        }
+    }
+}
+
+fn set_fat_entry(fat: &mut Fat, cluster: u32, value: u32) -> Result<()> {
+    //every copy of the FAT has to agree, otherwise fsck will complain, unless FAT32 only
+    //uses one of them
+    let copies = match fat.active_fat {
+        Some(active_fat) => active_fat as u64..active_fat as u64 + 1,
+        None => 0..fat.num_fats as u64,
+    };
+    for copy in copies {
+        let fat_offset = get_fat_offset(fat) + copy * get_fat_size(fat);
+        match fat.fat_type {
+            FatType::Fat12 => {
+                //the other half of the shared byte belongs to the neighbouring cluster
+                let offset = fat_offset + cluster as u64 * 3 / 2;
+                let fat_entry_temp: &mut [u8] = &mut [0; 2];
+                fat.device.read_at(offset, fat_entry_temp)?;
+                let mut entry = LittleEndian::read_u16(fat_entry_temp);
+                if cluster % 2 == 1 {
+                    entry = (entry & 0x000F) | ((value as u16) << 4);
+                } else {
+                    entry = (entry & 0xF000) | (value as u16 & 0x0FFF);
+                }
+                fat.device.write_at(offset, &entry.to_le_bytes())?;
+            }
+            FatType::Fat16 => {
+                fat.device.write_at(
+                    fat_offset + cluster as u64 * 2,
+                    &(value as u16).to_le_bytes(),
+                )?;
+            }
+            FatType::Fat32 => {
+                //the reserved top 4 bits have to be kept as they are
+                let offset = fat_offset + cluster as u64 * 4;
+                let fat_entry_temp: &mut [u8] = &mut [0; 4];
+                fat.device.read_at(offset, fat_entry_temp)?;
+                let entry =
+                    (LittleEndian::read_u32(fat_entry_temp) & 0xF000_0000) | (value & 0x0FFF_FFFF);
+                fat.device.write_at(offset, &entry.to_le_bytes())?;
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+fn get_dir_entry_offsets(fat: &Fat, starting_cluster: u32) -> Result<Vec<u64>> {
+    let mut offsets: Vec<u64> = Vec::new();
+
+    if starting_cluster == 0 && fat.fat_type != FatType::Fat32 {
+        //the root directory has a fixed size region of its own, it is not in the data region
+        let root_dir_offset = get_root_dir_offset(fat);
+        for entry in 0..fat.root_entries as u64 {
+            offsets.push(root_dir_offset + entry * 32);
+        }
+    } else {
+        //on FAT32 the root directory is a cluster chain like any other directory
+        let starting_cluster = match starting_cluster {
+            0 => fat.root_cluster,
+            cluster => cluster,
+        };
+        let cluster_size = fat.sectors_per_cluster as u64 * fat.sector_size as u64;
+        for cluster in get_cluster_chain(fat, starting_cluster)? {
+            let cluster_offset = get_cluster_offset(fat, cluster);
+            for entry in 0..cluster_size / 32 {
+                offsets.push(cluster_offset + entry * 32);
+            }
+        }
+    }
+
+    return Ok(offsets);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a FAT16 volume of 4267 sectors: 1 reserved sector, 2 FATs of 17 sectors and a 512 entry
+    //root directory, which leaves 4200 clusters of 1 sector
+    fn fat16_image() -> Vec<u8> {
+        let mut image = vec![0; 4267 * 512];
+        LittleEndian::write_u16(&mut image[BPB_BytsPerSec as usize..], 512);
+        image[BPB_SecPerClus as usize] = 1;
+        LittleEndian::write_u16(&mut image[BPB_RsvdSecCnt as usize..], 1);
+        image[BPB_NumFATs as usize] = 2;
+        LittleEndian::write_u16(&mut image[BPB_RootEntCnt as usize..], 512);
+        LittleEndian::write_u16(&mut image[BPB_TotSec16 as usize..], 4267);
+        LittleEndian::write_u16(&mut image[BPB_FATSz16 as usize..], 17);
+        return image;
+    }
+
+    //a 64 sector FAT12 volume: 1 reserved sector, 2 FATs of 1 sector and a 16 entry root
+    //directory, which leaves 60 clusters of 1 sector
+    fn fat12_image() -> Vec<u8> {
+        let mut image = vec![0; 64 * 512];
+        LittleEndian::write_u16(&mut image[BPB_BytsPerSec as usize..], 512);
+        image[BPB_SecPerClus as usize] = 1;
+        LittleEndian::write_u16(&mut image[BPB_RsvdSecCnt as usize..], 1);
+        image[BPB_NumFATs as usize] = 2;
+        LittleEndian::write_u16(&mut image[BPB_RootEntCnt as usize..], 16);
+        LittleEndian::write_u16(&mut image[BPB_TotSec16 as usize..], 64);
+        LittleEndian::write_u16(&mut image[BPB_FATSz16 as usize..], 1);
+        return image;
+    }
+
+    //a FAT32 volume of 66658 sectors: 32 reserved sectors with FSInfo in sector 1, 2 FATs of
+    //513 sectors and 65600 clusters of 1 sector, the root directory starting at cluster 5
+    fn fat32_image() -> Vec<u8> {
+        let mut image = vec![0; 66658 * 512];
+        LittleEndian::write_u16(&mut image[BPB_BytsPerSec as usize..], 512);
+        image[BPB_SecPerClus as usize] = 1;
+        LittleEndian::write_u16(&mut image[BPB_RsvdSecCnt as usize..], 32);
+        image[BPB_NumFATs as usize] = 2;
+        LittleEndian::write_u32(&mut image[BPB_TotSec32 as usize..], 66658);
+        LittleEndian::write_u32(&mut image[BPB_FATSz32 as usize..], 513);
+        LittleEndian::write_u32(&mut image[BPB_RootClus as usize..], 5);
+        LittleEndian::write_u16(&mut image[BPB_FSInfo as usize..], 1);
+
+        LittleEndian::write_u32(&mut image[512 + FSI_LeadSig as usize..], 0x4161_5252);
+        LittleEndian::write_u32(&mut image[512 + FSI_StrucSig as usize..], 0x6141_7272);
+        LittleEndian::write_u32(&mut image[512 + FSI_Free_Count as usize..], 1000);
+        LittleEndian::write_u32(&mut image[512 + FSI_Nxt_Free as usize..], 300);
+        return image;
+    }
+
+    fn short_entry(name: &[u8; 11], attributes: u8, cluster: u32, size: u32) -> [u8; 32] {
+        let mut entry = [0; 32];
+        entry[..11].copy_from_slice(name);
+        entry[11] = attributes;
+        LittleEndian::write_u16(&mut entry[20..], (cluster >> 16) as u16);
+        LittleEndian::write_u16(&mut entry[26..], cluster as u16);
+        LittleEndian::write_u32(&mut entry[28..], size);
+        return entry;
+    }
+
+    fn lfn_entry(sequence: u8, checksum: u8, part: &str) -> [u8; 32] {
+        //the part is terminated by 0x0000 if it is shorter than 13 characters, then padded
+        let mut chars: Vec<u16> = part.encode_utf16().collect();
+        if chars.len() < 13 {
+            chars.push(0);
+        }
+        chars.resize(13, 0xFFFF);
+
+        let mut entry = [0; 32];
+        entry[0] = sequence;
+        entry[11] = 0x0F;
+        entry[13] = checksum;
+        let slots = (1..11)
+            .step_by(2)
+            .chain((14..26).step_by(2))
+            .chain((28..32).step_by(2));
+        for (slot, c) in slots.zip(chars) {
+            LittleEndian::write_u16(&mut entry[slot..], c);
+        }
+        return entry;
+    }
+
+    fn write_entries(fat: &mut Fat, offset: u64, entries: &[[u8; 32]]) {
+        for (i, entry) in entries.iter().enumerate() {
+            fat.device.write_at(offset + i as u64 * 32, entry).unwrap();
+        }
+    }
+
+    //links the clusters into a chain and fills them with `fill`
+    fn write_chain(fat: &mut Fat, chain: &[u32], fill: u8) {
+        let end_of_chain = fat.fat_type.bad_cluster() + 8;
+        for (i, cluster) in chain.iter().enumerate() {
+            let next = chain.get(i + 1).copied().unwrap_or(end_of_chain);
+            set_fat_entry(fat, *cluster, next).unwrap();
+            fat.device
+                .write_at(get_cluster_offset(fat, *cluster), &[fill; 512])
+                .unwrap();
+        }
+    }
+
+    //the entry of `cluster` in one of the copies of a 16 bit FAT
+    fn fat16_copy_entry(fat: &Fat, copy: u64, cluster: u32) -> u16 {
+        let entry: &mut [u8] = &mut [0; 2];
+        let offset = get_fat_offset(fat) + copy * get_fat_size(fat) + cluster as u64 * 2;
+        fat.device.read_at(offset, entry).unwrap();
+        return LittleEndian::read_u16(entry);
+    }
+
+    fn read_image(fat: &Fat) -> Vec<u8> {
+        let mut image = vec![0; fat.device.size().unwrap() as usize];
+        fat.device.read_at(0, &mut image).unwrap();
+        return image;
+    }
+
+    fn root_names(fat: &Fat) -> Vec<String> {
+        return fat
+            .read_dir("/")
+            .unwrap()
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+    }
+
+    fn with_root_entries(image: Vec<u8>, entries: &[[u8; 32]]) -> Fat<'static> {
+        let mut fat = Fat::new(image).unwrap();
+        let root_dir_offset = get_root_dir_offset(&fat);
+        write_entries(&mut fat, root_dir_offset, entries);
+        return fat;
+    }
+
+    #[test]
+    fn short_name_checksum() {
+        assert_eq!(get_short_name_checksum(b"README  ", b"TXT"), 0x73);
+        assert_eq!(get_short_name_checksum(b"LONGFI~1", b"TXT"), 0xD4);
+    }
+
+    #[test]
+    fn long_names_are_read_from_a_whole_sequence() {
+        let checksum = get_short_name_checksum(b"LONGFI~1", b"TXT");
+        let fat = with_root_entries(
+            fat16_image(),
+            &[
+                lfn_entry(0x42, checksum, "ame.txt"),
+                lfn_entry(0x01, checksum, "A long file n"),
+                short_entry(b"LONGFI~1TXT", 0x20, 0, 0),
+            ],
+        );
+
+        assert_eq!(root_names(&fat), ["A long file name.txt"]);
+    }
+
+    #[test]
+    fn orphaned_long_name_entries_are_ignored() {
+        let checksum = get_short_name_checksum(b"LONGFI~1", b"TXT");
+        let fat = with_root_entries(
+            fat16_image(),
+            &[
+                //the first entry of the sequence is missing
+                lfn_entry(0x01, checksum, "A long file n"),
+                short_entry(b"LONGFI~1TXT", 0x20, 0, 0),
+                //the last entry of the sequence is missing
+                lfn_entry(0x42, checksum, "ame.txt"),
+                short_entry(b"LONGFI~1TXT", 0x20, 0, 0),
+                //the sequence belongs to another short name
+                lfn_entry(0x41, checksum, "Other name"),
+                short_entry(b"README  TXT", 0x20, 0, 0),
+                //a sequence left behind by a deleted file is followed by a new one
+                lfn_entry(0x42, 0, "stale"),
+                lfn_entry(0x41, checksum, "Short"),
+                short_entry(b"LONGFI~1TXT", 0x20, 0, 0),
+            ],
+        );
+
+        assert_eq!(
+            root_names(&fat),
+            ["LONGFI~1.TXT", "LONGFI~1.TXT", "README.TXT", "Short"]
+        );
+    }
+
+    //FILE.TXT in clusters 2, 3 and 7 and OTHER.TXT in cluster 4
+    fn fat16_with_files() -> Fat<'static> {
+        let mut fat = with_root_entries(
+            fat16_image(),
+            &[
+                short_entry(b"FILE    TXT", 0x20, 2, 1500),
+                short_entry(b"OTHER   TXT", 0x20, 4, 10),
+            ],
+        );
+        write_chain(&mut fat, &[2, 3, 7], 0xAB);
+        write_chain(&mut fat, &[4], 0xCD);
+        return fat;
+    }
+
+    #[test]
+    fn deleting_frees_the_chain_in_every_fat() {
+        let mut fat = fat16_with_files();
+        fat.remove("/FILE.TXT", false).unwrap();
+
+        assert_eq!(root_names(&fat), ["OTHER.TXT"]);
+        for copy in 0..2 {
+            for cluster in [2, 3, 7] {
+                assert_eq!(fat16_copy_entry(&fat, copy, cluster), 0);
+            }
+            assert_eq!(fat16_copy_entry(&fat, copy, 4), 0xFFFF);
+        }
+
+        //without --scrub the data is left where it was
+        let data: &mut [u8] = &mut [0; 512];
+        fat.device
+            .read_at(get_cluster_offset(&fat, 7), data)
+            .unwrap();
+        assert!(data.iter().all(|&b| b == 0xAB));
+    }
+
+    #[test]
+    fn scrubbing_zeroes_the_freed_clusters() {
+        let mut fat = fat16_with_files();
+        fat.remove("/FILE.TXT", true).unwrap();
+
+        let data: &mut [u8] = &mut [0; 512];
+        for cluster in [2, 3, 7] {
+            fat.device
+                .read_at(get_cluster_offset(&fat, cluster), data)
+                .unwrap();
+            assert!(data.iter().all(|&b| b == 0));
+        }
+        fat.device
+            .read_at(get_cluster_offset(&fat, 4), data)
+            .unwrap();
+        assert!(data.iter().all(|&b| b == 0xCD));
+    }
+
+    #[test]
+    fn a_corrupt_chain_is_refused_before_anything_is_written() {
+        let mut fat = fat16_with_files();
+        //cluster 3 points past the 4200 clusters of the volume
+        set_fat_entry(&mut fat, 3, 5000).unwrap();
+        let before = read_image(&fat);
+
+        assert!(matches!(
+            fat.remove("/FILE.TXT", false),
+            Err(Error::CorruptStructure(_))
+        ));
+        assert!(read_image(&fat) == before);
+    }
+
+    #[test]
+    fn the_32_bit_sector_count_is_used_when_the_16_bit_one_is_0() {
+        let mut image = fat16_image();
+        LittleEndian::write_u16(&mut image[BPB_TotSec16 as usize..], 0);
+        LittleEndian::write_u32(&mut image[BPB_TotSec32 as usize..], 4267);
+
+        let fat = Fat::new(image).unwrap();
+        assert_eq!(fat.total_sectors, 4267);
+        assert_eq!(fat.data_sectors, 4200);
+        assert_eq!(fat.cluster_count, 4200);
+    }
+
+    #[test]
+    fn small_volumes_are_fat12() {
+        let fat = Fat::new(fat12_image()).unwrap();
+        assert_eq!(fat.fat_type, FatType::Fat12);
+        assert_eq!(fat.cluster_count, 60);
+    }
+
+    #[test]
+    fn fat12_entries_leave_their_neighbours_intact() {
+        let mut fat = Fat::new(fat12_image()).unwrap();
+        set_fat_entry(&mut fat, 2, 0xABC).unwrap();
+        set_fat_entry(&mut fat, 3, 0x123).unwrap();
+        set_fat_entry(&mut fat, 4, 0xFFF).unwrap();
+        set_fat_entry(&mut fat, 5, 0x456).unwrap();
+
+        //cluster 2 is at byte 3 and cluster 3 at byte 4, they share the low half of byte 4
+        let raw: &mut [u8] = &mut [0; 6];
+        fat.device.read_at(get_fat_offset(&fat) + 3, raw).unwrap();
+        assert_eq!(raw, [0xBC, 0x3A, 0x12, 0xFF, 0x6F, 0x45]);
+
+        set_fat_entry(&mut fat, 3, 0).unwrap();
+        set_fat_entry(&mut fat, 4, 0x001).unwrap();
+        assert_eq!(get_fat_entry(&fat, 2).unwrap(), 0xABC);
+        assert_eq!(get_fat_entry(&fat, 3).unwrap(), 0);
+        assert_eq!(get_fat_entry(&fat, 4).unwrap(), 0x001);
+        assert_eq!(get_fat_entry(&fat, 5).unwrap(), 0x456);
+
+        //both copies of the FAT are kept the same
+        let first: &mut [u8] = &mut [0; 512];
+        let second: &mut [u8] = &mut [0; 512];
+        fat.device.read_at(get_fat_offset(&fat), first).unwrap();
+        fat.device
+            .read_at(get_fat_offset(&fat) + get_fat_size(&fat), second)
+            .unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn fat32_reads_the_root_directory_from_its_cluster_chain() {
+        //the root directory is clusters 5 and 9, 16 entries each
+        let mut fat = Fat::new(fat32_image()).unwrap();
+        assert_eq!(fat.fat_type, FatType::Fat32);
+        assert_eq!(fat.root_cluster, 5);
+        write_chain(&mut fat, &[5, 9], 0);
+
+        let entries: Vec<[u8; 32]> = (0..17)
+            .map(|i| {
+                let mut name = *b"FILE00  TXT";
+                name[4] += i / 10;
+                name[5] += i % 10;
+                return short_entry(&name, 0x20, 0, 0);
+            })
+            .collect();
+        let first_cluster = get_cluster_offset(&fat, 5);
+        write_entries(&mut fat, first_cluster, &entries[..16]);
+        let second_cluster = get_cluster_offset(&fat, 9);
+        write_entries(&mut fat, second_cluster, &entries[16..]);
+
+        let names = root_names(&fat);
+        assert_eq!(names.len(), 17);
+        assert_eq!(names[0], "FILE00.TXT");
+        assert_eq!(names[16], "FILE16.TXT");
+    }
+
+    #[test]
+    fn fat32_deletes_update_the_fs_info_hints() {
+        let mut fat = Fat::new(fat32_image()).unwrap();
+        assert_eq!(fat.free_clusters, Some(1000));
+        assert_eq!(fat.next_free_cluster, Some(300));
+        write_chain(&mut fat, &[5], 0);
+        let root_dir_offset = get_cluster_offset(&fat, 5);
+        write_entries(
+            &mut fat,
+            root_dir_offset,
+            &[
+                short_entry(b"LOW     TXT", 0x20, 0x1_0020, 1024),
+                short_entry(b"HIGH    TXT", 0x20, 200, 10),
+            ],
+        );
+        write_chain(&mut fat, &[0x1_0020, 0x1_0021], 0xAB);
+        write_chain(&mut fat, &[200], 0xAB);
+        //the reserved top bits of an entry are not part of the cluster number
+        set_fat_entry(&mut fat, 0x1_0021, 0x0FFF_FFFF).unwrap();
+        fat.device
+            .write_at(get_fat_offset(&fat) + 0x1_0021 * 4 + 3, &[0xFF])
+            .unwrap();
+
+        //the next free cluster only moves back
+        fat.remove("/HIGH.TXT", false).unwrap();
+        assert_eq!(fat.free_clusters, Some(1001));
+        assert_eq!(fat.next_free_cluster, Some(200));
+
+        fat.remove("/LOW.TXT", false).unwrap();
+        assert_eq!(fat.free_clusters, Some(1003));
+        assert_eq!(fat.next_free_cluster, Some(200));
+        assert_eq!(get_fat_entry(&fat, 0x1_0020).unwrap(), 0);
+        assert_eq!(get_fat_entry(&fat, 0x1_0021).unwrap(), 0);
+
+        let raw: &mut [u8] = &mut [0; 4];
+        fat.device
+            .read_at(get_fat_offset(&fat) + 0x1_0021 * 4, raw)
+            .unwrap();
+        assert_eq!(LittleEndian::read_u32(raw), 0xF000_0000);
+
+        //the hints are written to FSInfo, where the next load finds them
+        let fat = Fat::new(read_image(&fat)).unwrap();
+        assert_eq!(fat.free_clusters, Some(1003));
+        assert_eq!(fat.next_free_cluster, Some(200));
+    }
+}
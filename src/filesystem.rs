@@ -1,12 +1,340 @@
-use std::str;
+use crate::error::{Error, Result};
+use crate::pattern::*;
+use crate::utilities;
+use chrono::NaiveDateTime;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Read;
 
+/// The kind of object a directory entry points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileType::File => write!(f, "file"),
+            FileType::Directory => write!(f, "directory"),
+            FileType::Symlink => write!(f, "symlink"),
+            FileType::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// What `stat` and `read_dir` know about a file.
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    pub name: String,
+    //only FAT has a second (8.3) name for the same file
+    pub short_name: Option<String>,
+    pub file_type: FileType,
+    pub size: u64,
+    //the inode number on EXT2, the starting cluster on FAT
+    pub id: u64,
+    pub modified: Option<NaiveDateTime>,
+    //the filesystem specific columns of /ls, as label/value pairs
+    pub details: Vec<(String, String)>,
+}
+
+/// A file matched by `find`, with the path it was found at.
+#[derive(Debug, Clone)]
+pub struct FoundFile {
+    pub path: String,
+    pub metadata: Metadata,
+}
+
+/// Information about the volume, as titled groups of label/value pairs.
+#[derive(Debug, Clone)]
+pub struct VolumeInfo {
+    pub filesystem: String,
+    pub sections: Vec<InfoSection>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InfoSection {
+    pub title: String,
+    pub fields: Vec<(String, String)>,
+}
+
+/// What every supported filesystem can do with a volume image.
+///
+/// Paths are absolute and use `/` as separator, `/` itself being the root directory.
 pub trait Filesystem {
-    fn load_info(&mut self, name: &str) -> &mut dyn Filesystem;
-    fn print_info(&mut self) -> &mut dyn Filesystem;
-    fn find(
-        &mut self,
-        file_to_find: &str,
-        name_of_file: &str,
-        delete_flag: bool,
-    ) -> &mut dyn Filesystem;
+    /// The information from the superblock/boot sector.
+    fn volume_info(&self) -> VolumeInfo;
+    /// Looks up the file or directory at `path`.
+    fn stat(&self, path: &str) -> Result<Metadata>;
+    /// Lists the directory at `path`, without `.` and `..`.
+    fn read_dir(&self, path: &str) -> Result<Vec<Metadata>>;
+    /// Opens the file at `path` for reading its contents.
+    fn open(&self, path: &str) -> Result<Box<dyn Read + '_>>;
+    /// Deletes the file at `path`, overwriting its data with zeros if `scrub_flag` is set.
+    fn remove(&mut self, path: &str, scrub_flag: bool) -> Result<()>;
+    /// Whether two names that only differ in case are different files.
+    fn case_sensitive(&self) -> bool;
+
+    /// Calls `visitor` with the path and metadata of every entry below the directory at
+    /// `path`, each directory right before its contents.
+    ///
+    /// Meeting the same directory twice, through a loop or a cross link, means the volume is
+    /// corrupt.
+    fn walk(
+        &self,
+        path: &str,
+        visitor: &mut dyn FnMut(&str, &Metadata) -> Result<()>,
+    ) -> Result<()> {
+        let mut visited: HashSet<u64> = HashSet::new();
+        visited.insert(self.stat(path)?.id);
+        return walk_dir(self, path, visitor, &mut visited);
+    }
+
+    /// Searches the whole volume for every file whose name matches `pattern`.
+    ///
+    /// On FAT the short name is tried as well.
+    fn find(&self, pattern: &Pattern) -> Result<Vec<FoundFile>> {
+        let case_sensitive = self.case_sensitive();
+        let mut found: Vec<FoundFile> = Vec::new();
+
+        self.walk("/", &mut |path, metadata| {
+            let short_name_matches = match &metadata.short_name {
+                Some(short_name) => pattern.matches(short_name, case_sensitive),
+                None => false,
+            };
+
+            if metadata.file_type != FileType::Directory
+                && (pattern.matches(&metadata.name, case_sensitive) || short_name_matches)
+            {
+                found.push(FoundFile {
+                    path: path.to_string(),
+                    metadata: metadata.clone(),
+                });
+            }
+
+            return Ok(());
+        })?;
+
+        return Ok(found);
+    }
+
+    /// Turns a pattern or a path into the existing entries it names.
+    ///
+    /// A plain name with a `/` is resolved component by component from the root and names
+    /// at most one entry, anything else is searched for with `find` and can match several.
+    fn resolve(&self, pattern: &Pattern) -> Result<Vec<FoundFile>> {
+        let file_to_find = match pattern {
+            Pattern::Name(name) if name.contains('/') => name,
+            _ => {
+                let found = self.find(pattern)?;
+                if found.is_empty() {
+                    return Err(Error::NotFound(pattern.to_string()));
+                }
+
+                return Ok(found);
+            }
+        };
+
+        //stat every prefix so the path uses the names as stored on the volume
+        let components = utilities::split_path(file_to_find);
+        let mut path = String::new();
+        let mut metadata = self.stat("/")?;
+        for i in 0..components.len() {
+            metadata = match self.stat(&components[..=i].join("/")) {
+                Err(Error::NotFound(_)) => return Err(Error::NotFound(file_to_find.to_string())),
+                result => result?,
+            };
+            path = utilities::join_path(&path, &metadata.name);
+        }
+
+        if path.is_empty() {
+            path.push('/');
+        }
+
+        return Ok(vec![FoundFile { path, metadata }]);
+    }
+}
+
+//directories can't have hard links, so a directory id seen before means two entries share it, and
+//following the second one could loop back up the tree forever
+fn walk_dir<F: Filesystem + ?Sized>(
+    fs: &F,
+    path: &str,
+    visitor: &mut dyn FnMut(&str, &Metadata) -> Result<()>,
+    visited: &mut HashSet<u64>,
+) -> Result<()> {
+    for metadata in fs.read_dir(path)? {
+        let entry_path = utilities::join_path(path, &metadata.name);
+        visitor(&entry_path, &metadata)?;
+
+        if metadata.file_type == FileType::Directory {
+            if !visited.insert(metadata.id) {
+                return Err(Error::CorruptStructure(format!(
+                    "the directory {} was already visited under another path",
+                    entry_path
+                )));
+            }
+
+            walk_dir(fs, &entry_path, visitor, visited)?;
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io;
+
+    //a case insensitive volume held as the entries of each directory, keyed by the lowercase
+    //path of the directory
+    struct Tree {
+        dirs: HashMap<String, Vec<Metadata>>,
+    }
+
+    fn entry(name: &str, file_type: FileType, id: u64) -> Metadata {
+        return Metadata {
+            name: name.to_string(),
+            short_name: None,
+            file_type,
+            size: 0,
+            id,
+            modified: None,
+            details: Vec::new(),
+        };
+    }
+
+    impl Tree {
+        fn new(dirs: &[(&str, Vec<Metadata>)]) -> Tree {
+            let dirs = dirs
+                .iter()
+                .map(|(path, entries)| (path.to_lowercase(), entries.clone()))
+                .collect();
+            return Tree { dirs };
+        }
+    }
+
+    impl Filesystem for Tree {
+        fn volume_info(&self) -> VolumeInfo {
+            return VolumeInfo {
+                filesystem: "tree".to_string(),
+                sections: Vec::new(),
+            };
+        }
+
+        fn stat(&self, path: &str) -> Result<Metadata> {
+            let components = utilities::split_path(path);
+            let (name, parent) = match components.split_last() {
+                Some((name, parent)) => (name, parent),
+                None => return Ok(entry("/", FileType::Directory, 1)),
+            };
+
+            return self
+                .read_dir(&format!("/{}", parent.join("/")))?
+                .into_iter()
+                .find(|metadata| metadata.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| Error::NotFound(path.to_string()));
+        }
+
+        fn read_dir(&self, path: &str) -> Result<Vec<Metadata>> {
+            let path = format!("/{}", utilities::split_path(path).join("/"));
+            return match self.dirs.get(&path.to_lowercase()) {
+                Some(entries) => Ok(entries.clone()),
+                None => Err(Error::NotFound(path)),
+            };
+        }
+
+        fn open(&self, _path: &str) -> Result<Box<dyn Read + '_>> {
+            return Ok(Box::new(io::empty()));
+        }
+
+        fn remove(&mut self, path: &str, _scrub_flag: bool) -> Result<()> {
+            return Err(Error::Unsupported(path.to_string()));
+        }
+
+        fn case_sensitive(&self) -> bool {
+            return false;
+        }
+    }
+
+    //the same name in two directories
+    fn duplicates() -> Tree {
+        return Tree::new(&[
+            (
+                "/",
+                vec![
+                    entry("Docs", FileType::Directory, 10),
+                    entry("misc", FileType::Directory, 11),
+                ],
+            ),
+            ("/docs", vec![entry("Notes.txt", FileType::File, 20)]),
+            (
+                "/misc",
+                vec![
+                    entry("notes.TXT", FileType::File, 21),
+                    entry("other.txt", FileType::File, 22),
+                ],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn names_are_searched_everywhere() {
+        let found = duplicates()
+            .resolve(&Pattern::new("NOTES.txt").unwrap())
+            .unwrap();
+        let found: Vec<(&str, u64)> = found
+            .iter()
+            .map(|found| (found.path.as_str(), found.metadata.id))
+            .collect();
+        assert_eq!(found, [("/Docs/Notes.txt", 20), ("/misc/notes.TXT", 21)]);
+    }
+
+    #[test]
+    fn paths_name_a_single_entry() {
+        let tree = duplicates();
+        let found = tree
+            .resolve(&Pattern::new("/docs/NOTES.TXT").unwrap())
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, "/Docs/Notes.txt");
+        assert_eq!(found[0].metadata.id, 20);
+
+        let found = tree.resolve(&Pattern::new("/MISC/").unwrap()).unwrap();
+        assert_eq!(found[0].path, "/misc");
+        assert_eq!(found[0].metadata.file_type, FileType::Directory);
+
+        assert!(matches!(
+            tree.resolve(&Pattern::new("/docs/other.txt").unwrap()),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            tree.resolve(&Pattern::new("missing.txt").unwrap()),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn walking_into_the_same_directory_twice_is_corrupt() {
+        let tree = Tree::new(&[
+            ("/", vec![entry("a", FileType::Directory, 10)]),
+            ("/a", vec![entry("b", FileType::Directory, 11)]),
+            //b/c is a second entry for a, so the walk would never end
+            ("/a/b", vec![entry("c", FileType::Directory, 10)]),
+            ("/a/b/c", vec![entry("b", FileType::Directory, 11)]),
+        ]);
+
+        let mut visited: Vec<String> = Vec::new();
+        let result = tree.walk("/", &mut |path, _| {
+            visited.push(path.to_string());
+            return Ok(());
+        });
+        assert!(matches!(result, Err(Error::CorruptStructure(_))));
+        assert_eq!(visited, ["/a", "/a/b", "/a/b/c"]);
+    }
 }
@@ -0,0 +1,29 @@
+//! Parsers for FAT12, FAT16, FAT32 and EXT2 volume images.
+//!
+//! `checker::check_file` looks at an image and returns the matching `Filesystem`
+//! (`Fat` or `Ext2`), which can then report the volume information, find, delete
+//! and read files. `checker::check_device` does the same for any `BlockDevice`,
+//! such as an image already loaded in memory. `checker::check_ext2_file` returns
+//! the `Ext2` itself, for what only EXT2 has. `detect::detect` only tells what a
+//! volume looks like, and why.
+#![allow(clippy::needless_return)]
+pub mod checker;
+pub mod detect;
+pub mod device;
+pub mod error;
+pub mod ext2;
+pub mod fat;
+//the FAT module was called fat16 before it read FAT12 and FAT32 too
+pub use fat as fat16;
+pub mod filesystem;
+pub mod pattern;
+pub mod utilities;
+
+pub use checker::{check_device, check_ext2_file, check_file};
+pub use detect::{detect, Detection, FilesystemType};
+pub use device::BlockDevice;
+pub use error::{Error, Result};
+pub use ext2::Ext2;
+pub use fat::{Fat, Fat16};
+pub use filesystem::Filesystem;
+pub use pattern::Pattern;
@@ -1,62 +1,603 @@
+#![allow(clippy::needless_return)]
 extern crate clap;
-mod checker;
-mod ext2;
-mod fat16;
-mod filesystem;
-mod utilities;
-use clap::{App, Arg};
+use aos_fat16_ext2_2021::ext2::{BlockRef, Inode};
+use aos_fat16_ext2_2021::filesystem::{FileType, FoundFile, Metadata};
+use aos_fat16_ext2_2021::utilities;
+use aos_fat16_ext2_2021::{checker, detect, Error, Filesystem, Pattern, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use clap::{App, Arg, ArgMatches};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::process;
 
 fn main() {
-    let matches = App::new("AOS The Shooter")
-        .author("Felipe Perez <fpstoppa@gmail.com>")
-        .arg(
-            Arg::with_name("operation")
-                .takes_value(true)
-                .help("The desired operation"),
-        )
-        .arg(
-            Arg::with_name("file")
-                .takes_value(true)
-                .help("The volume to be scanned"),
-        )
-        .arg(
-            Arg::with_name("file_to_find")
-                .takes_value(true)
-                .help("The file to find (for option /find)"),
-        )
-        .get_matches();
+    let matches =
+        App::new("AOS The Shooter")
+            .author("Felipe Perez <fpstoppa@gmail.com>")
+            .arg(
+                Arg::with_name("operation")
+                    .takes_value(true)
+                    .help("The desired operation"),
+            )
+            .arg(
+                Arg::with_name("file")
+                    .takes_value(true)
+                    .help("The volume to be scanned"),
+            )
+            .arg(Arg::with_name("file_to_find").takes_value(true).help(
+                "The name or path of the file (for options /find, /delete, /cat, /extract, /ls, /tree and /stat), or the copy to write (for option /replay)",
+            ))
+            .arg(
+                Arg::with_name("destination")
+                    .takes_value(true)
+                    .help("Where to write the extracted file (for option /extract)"),
+            )
+            .arg(Arg::with_name("scrub").long("scrub").help(
+                "Also overwrite the data of the deleted file with zeros (for option /delete)",
+            ))
+            .arg(
+                Arg::with_name("all")
+                    .long("all")
+                    .help("Delete every file that matches instead of asking (for option /delete)"),
+            )
+            .arg(
+                Arg::with_name("regex")
+                    .long("regex")
+                    .help("Treat the file to find as a regular expression"),
+            )
+            .arg(
+                Arg::with_name("modified_after")
+                    .long("modified-after")
+                    .takes_value(true)
+                    .value_name("DATE")
+                    .help("Only files modified at or after DATE (for options /find and /delete)"),
+            )
+            .arg(
+                Arg::with_name("modified_before")
+                    .long("modified-before")
+                    .takes_value(true)
+                    .value_name("DATE")
+                    .help("Only files modified before DATE (for options /find and /delete)"),
+            )
+            .get_matches();
+
+    if let Err(e) = run(&matches) {
+        eprintln!("{}", e);
+        process::exit(e.exit_code());
+    }
+}
 
+fn run(matches: &ArgMatches) -> Result<()> {
     let myfile = matches.value_of("file").unwrap_or("");
     let file_to_find = matches.value_of("file_to_find").unwrap_or("");
+    let destination = matches.value_of("destination").unwrap_or("");
+    let scrub_flag = matches.is_present("scrub");
+    let all_flag = matches.is_present("all");
+    let regex_flag = matches.is_present("regex");
+    let date_range = (
+        get_date(matches.value_of("modified_after"))?,
+        get_date(matches.value_of("modified_before"))?,
+    );
     let operation = matches.value_of("operation");
 
     match operation {
-        None => println!("No operation passed!"),
+        None => {
+            println!("No operation passed!");
+            process::exit(1);
+        }
         Some(s) => match s {
+            "/find" | "/delete" | "/cat" | "/extract" | "/stat" | "/replay"
+                if file_to_find.is_empty() || file_to_find.contains('\0') =>
+            {
+                return Err(Error::InvalidName(file_to_find.to_string()));
+            }
+            "/extract" if destination.is_empty() || destination.contains('\0') => {
+                return Err(Error::InvalidName(destination.to_string()));
+            }
             "/info" => {
-                //if selected option is info, run the function that gets
-                checker::check_file(myfile)
-                    .as_mut()
-                    .load_info(myfile)
-                    .print_info();
+                //if selected option is info, print why the volume was taken for what it is,
+                //then every section the filesystem reports
+                let detection = detect::detect(&File::open(myfile)?)?;
+                println!("\n------ Filesystem Information ------\n");
+                if detection.confidence < detect::MIN_CONFIDENCE {
+                    println!(
+                        "Detected: nothing, at best {} ({}% confidence)",
+                        detection.filesystem_type, detection.confidence
+                    );
+                } else {
+                    println!(
+                        "Detected: {} ({}% confidence)",
+                        detection.filesystem_type, detection.confidence
+                    );
+                }
+                for evidence in &detection.evidence {
+                    println!(
+                        "  {} {}",
+                        if evidence.passed { "+" } else { "-" },
+                        evidence.description
+                    );
+                }
+                println!();
+
+                let volume_info = checker::check_file(myfile)?.volume_info();
+                println!("Filesystem: {}\n", volume_info.filesystem);
+                for section in volume_info.sections {
+                    if !section.title.is_empty() {
+                        println!("{}", section.title);
+                    }
+                    for (label, value) in section.fields {
+                        println!("{}: {}", label, value);
+                    }
+                    println!();
+                }
             }
             "/find" => {
-                //if selected option is info, run the function that gets
-                checker::check_file(myfile).as_mut().load_info(myfile).find(
-                    file_to_find,
-                    myfile,
-                    false,
-                );
+                let pattern = get_pattern(file_to_find, regex_flag)?;
+                let fs = checker::check_file(myfile)?;
+                let found = filter_by_date(fs.resolve(&pattern)?, &date_range, file_to_find)?;
+
+                println!("Found {} file(s):", found.len());
+                for FoundFile { path, metadata } in found {
+                    match metadata.short_name {
+                        Some(short_name) if short_name != metadata.name => {
+                            println!("{} ({})", path, short_name)
+                        }
+                        _ => println!("{}", path),
+                    }
+                    match metadata.modified {
+                        Some(modified) => println!(
+                            "    {}, {} bytes, modified {}",
+                            metadata.file_type,
+                            metadata.size,
+                            modified.format("%Y-%m-%d %H:%M:%S")
+                        ),
+                        None => println!("    {}, {} bytes", metadata.file_type, metadata.size),
+                    }
+                    let details: Vec<String> = metadata
+                        .details
+                        .iter()
+                        .map(|(label, value)| format!("{}: {}", label, value))
+                        .collect();
+                    println!("    {}", details.join(", "));
+                }
+            }
+            "/ls" => {
+                //without a path the root directory is listed
+                let path = if file_to_find.is_empty() {
+                    "/"
+                } else {
+                    file_to_find
+                };
+                let fs = checker::check_file(myfile)?;
+                let metadata = fs.stat(path)?;
+                let entries = if metadata.file_type == FileType::Directory {
+                    fs.read_dir(path)?
+                } else {
+                    vec![metadata]
+                };
+
+                print_listing(&entries);
+            }
+            "/tree" => {
+                //without a path the whole volume is shown
+                let path = if file_to_find.is_empty() {
+                    "/"
+                } else {
+                    file_to_find
+                };
+                let fs = checker::check_file(myfile)?;
+                print_tree(fs.as_ref(), path)?;
+            }
+            "/stat" => {
+                let ext2 = checker::check_ext2_file(myfile)?;
+                print_inode(&ext2.inode(file_to_find)?);
             }
             "/delete" => {
-                //if selected option is info, run the function that gets
-                checker::check_file(myfile).as_mut().load_info(myfile).find(
-                    file_to_find,
-                    myfile,
-                    true,
-                );
-            }
-            _ => println!("Invalid operation {}", s),
+                let pattern = get_pattern(file_to_find, regex_flag)?;
+                let mut fs = checker::check_file(myfile)?;
+                let found = filter_by_date(fs.resolve(&pattern)?, &date_range, file_to_find)?;
+                for found in choose(found, "delete", true, all_flag)? {
+                    fs.remove(&found.path, scrub_flag)?;
+                    println!("File Succesfully deleted: {}", found.path);
+                }
+            }
+            "/cat" => {
+                let pattern = get_pattern(file_to_find, regex_flag)?;
+                //write the contents of the file to stdout
+                let fs = checker::check_file(myfile)?;
+                for found in choose(fs.resolve(&pattern)?, "print", false, false)? {
+                    io::copy(&mut fs.open(&found.path)?, &mut io::stdout())?;
+                }
+            }
+            "/extract" => {
+                let pattern = get_pattern(file_to_find, regex_flag)?;
+                //write the contents of the file to a file on the host
+                let fs = checker::check_file(myfile)?;
+                for found in choose(fs.resolve(&pattern)?, "extract", false, false)? {
+                    let mut reader = fs.open(&found.path)?;
+                    let mut output = File::create(destination)?;
+                    io::copy(&mut reader, &mut output)?;
+                }
+            }
+            "/replay" => {
+                //the volume itself is never written, the journal is replayed into a copy of it
+                let copy = file_to_find;
+                checker::check_ext2_file(myfile)?.journal()?;
+
+                //the copy must be a new file, so nothing already on the host is overwritten
+                let mut input = File::open(myfile)?;
+                let mut output = OpenOptions::new().write(true).create_new(true).open(copy)?;
+                let replayed = io::copy(&mut input, &mut output)
+                    .map_err(Error::from)
+                    .and_then(|_| checker::check_ext2_file(copy))
+                    .and_then(|mut ext2| ext2.replay_journal());
+                match replayed {
+                    Ok(replayed) => {
+                        println!("Replayed {} transaction(s) into {}", replayed, copy)
+                    }
+                    Err(e) => {
+                        //a half replayed copy is worse than none
+                        fs::remove_file(copy)?;
+                        return Err(e);
+                    }
+                }
+            }
+            _ => {
+                println!("Invalid operation {}", s);
+                process::exit(1);
+            }
         },
     }
+
+    return Ok(());
+}
+
+//one line per entry, the filesystem specific columns first and the name last
+fn print_listing(entries: &[Metadata]) {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    if let Some(first) = entries.first() {
+        let mut header: Vec<String> = first
+            .details
+            .iter()
+            .map(|(label, _)| label.clone())
+            .collect();
+        header.extend(vec![
+            String::from("Size"),
+            String::from("Modified"),
+            String::from("Name"),
+        ]);
+        rows.push(header);
+    }
+
+    for entry in entries {
+        let mut row: Vec<String> = entry
+            .details
+            .iter()
+            .map(|(_, value)| value.clone())
+            .collect();
+        row.push(entry.size.to_string());
+        row.push(match entry.modified {
+            Some(modified) => modified.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => String::from("-"),
+        });
+        match entry.file_type {
+            FileType::Directory => row.push(format!("{}/", entry.name)),
+            _ => row.push(entry.name.clone()),
+        }
+        rows.push(row);
+    }
+
+    //every column is as wide as its widest value
+    let mut widths: Vec<usize> = Vec::new();
+    for row in &rows {
+        for (i, value) in row.iter().enumerate() {
+            if i >= widths.len() {
+                widths.push(0);
+            }
+            widths[i] = widths[i].max(value.chars().count());
+        }
+    }
+
+    for row in rows {
+        let mut line = String::new();
+        for (i, value) in row.iter().enumerate() {
+            if i == row.len() - 1 {
+                line.push_str(value);
+            } else {
+                line.push_str(&format!("{:<width$}  ", value, width = widths[i]));
+            }
+        }
+        println!("{}", line);
+    }
+}
+
+//the directory and everything below it, each directory with the total size and number of
+//files it holds, counting its subdirectories
+fn print_tree(fs: &dyn Filesystem, path: &str) -> Result<()> {
+    let root_path = format!("/{}", utilities::split_path(path).join("/"));
+    if fs.stat(&root_path)?.file_type != FileType::Directory {
+        return Err(Error::Unsupported(format!(
+            "{} is not a directory, only directories have a tree",
+            path
+        )));
+    }
+
+    let mut entries: Vec<(String, Metadata)> = Vec::new();
+    fs.walk(&root_path, &mut |entry_path, metadata| {
+        entries.push((entry_path.to_string(), metadata.clone()));
+        return Ok(());
+    })?;
+
+    //every file counts for all the directories above it, up to the one the tree starts at
+    let mut totals: HashMap<String, (u64, u64)> = HashMap::new();
+    for (entry_path, metadata) in &entries {
+        if metadata.file_type == FileType::Directory {
+            continue;
+        }
+
+        let mut dir_path = entry_path.as_str();
+        while let Some(end) = dir_path.rfind('/') {
+            dir_path = &entry_path[..end];
+            let key = if dir_path.is_empty() { "/" } else { dir_path };
+            let total = totals.entry(key.to_string()).or_default();
+            total.0 += 1;
+            total.1 += metadata.size;
+
+            if key == root_path {
+                break;
+            }
+        }
+    }
+
+    let get_depth = |entry_path: &str| {
+        utilities::split_path(entry_path).len() - utilities::split_path(&root_path).len()
+    };
+    let get_totals = |dir_path: &str| {
+        let (files, size) = totals.get(dir_path).copied().unwrap_or_default();
+        let noun = if files == 1 { "file" } else { "files" };
+        format!("({} {}, {} bytes)", files, noun, size)
+    };
+
+    println!("{} {}", root_path, get_totals(&root_path));
+
+    //whether the directories above the current entry were the last of their own directory
+    let mut last_flags: Vec<bool> = Vec::new();
+    for (i, (entry_path, metadata)) in entries.iter().enumerate() {
+        let depth = get_depth(entry_path);
+        let is_last = match entries[i + 1..]
+            .iter()
+            .map(|(next_path, _)| get_depth(next_path))
+            .find(|next_depth| *next_depth <= depth)
+        {
+            Some(next_depth) => next_depth < depth,
+            None => true,
+        };
+
+        last_flags.truncate(depth - 1);
+        let mut line = String::new();
+        for last in &last_flags {
+            line.push_str(if *last { "    " } else { "│   " });
+        }
+        line.push_str(if is_last { "└── " } else { "├── " });
+        last_flags.push(is_last);
+
+        if metadata.file_type == FileType::Directory {
+            println!("{}{}/ {}", line, metadata.name, get_totals(entry_path));
+        } else {
+            println!("{}{} ({} bytes)", line, metadata.name, metadata.size);
+        }
+    }
+
+    return Ok(());
+}
+
+//the same layout as the stat command of debugfs
+fn print_inode(inode: &Inode) {
+    let file_type = match inode.mode & 0xF000 {
+        0x8000 => "regular",
+        0x4000 => "directory",
+        0xA000 => "symlink",
+        0x2000 => "character special",
+        0x6000 => "block special",
+        0x1000 => "FIFO",
+        0xC000 => "socket",
+        _ => "bad type",
+    };
+    let format_time = |time: u32| {
+        format!(
+            "0x{:08x} -- {}",
+            time,
+            utilities::convert_to_utc_time(time.to_le_bytes()).format("%a %b %e %H:%M:%S %Y")
+        )
+    };
+
+    println!(
+        "Inode: {}   Type: {}    Mode:  {:04o}   Flags: 0x{:x}",
+        inode.number,
+        file_type,
+        inode.mode & 0o7777,
+        inode.flags
+    );
+    println!(
+        "Generation: {}    Version: 0x{:08x}",
+        inode.generation, inode.version
+    );
+    println!(
+        "User: {:5}   Group: {:5}   Size: {}",
+        inode.uid, inode.gid, inode.size
+    );
+    println!("File ACL: {}", inode.file_acl);
+    println!(
+        "Links: {}   Blockcount: {}",
+        inode.links_count, inode.blocks
+    );
+    println!(
+        "Fragment:  Address: {}    Number: {}    Size: {}",
+        inode.faddr, inode.frag, inode.fsize
+    );
+    println!("ctime: {}", format_time(inode.ctime));
+    println!("atime: {}", format_time(inode.atime));
+    println!("mtime: {}", format_time(inode.mtime));
+    if inode.dtime != 0 {
+        println!("dtime: {}", format_time(inode.dtime));
+    }
+
+    //a fast symlink has no blocks, its target is stored where the block pointers would be
+    if inode.fast_symlink {
+        let target: Vec<u8> = inode
+            .block
+            .iter()
+            .flat_map(|block| block.to_le_bytes())
+            .take(inode.size as usize)
+            .collect();
+        println!("Fast link dest: \"{}\"", String::from_utf8_lossy(&target));
+        return;
+    }
+
+    //runs of consecutive data blocks are shown as (first-last logical):first-last physical
+    let mut runs: Vec<String> = Vec::new();
+    let mut run: Option<(u64, u64, u64)> = None;
+    for block_ref in &inode.block_list {
+        match (*block_ref, run) {
+            (BlockRef::Data { logical, block }, Some((start, first, count)))
+                if start + count == logical && first + count == block =>
+            {
+                run = Some((start, first, count + 1));
+                continue;
+            }
+            _ => {}
+        }
+
+        if let Some(run) = run.take() {
+            runs.push(format_run(run));
+        }
+        match *block_ref {
+            BlockRef::Data { logical, block } => run = Some((logical, block, 1)),
+            BlockRef::Indirect { depth, block } => {
+                let label = ["IND", "DIND", "TIND"][depth as usize - 1];
+                runs.push(format!("({}):{}", label, block));
+            }
+        }
+    }
+    if let Some(run) = run {
+        runs.push(format_run(run));
+    }
+
+    println!("BLOCKS:");
+    println!("{}", runs.join(", "));
+    println!("TOTAL: {}", inode.block_list.len());
+}
+
+fn format_run((start, first, count): (u64, u64, u64)) -> String {
+    if count == 1 {
+        return format!("({}):{}", start, first);
+    }
+
+    return format!(
+        "({}-{}):{}-{}",
+        start,
+        start + count - 1,
+        first,
+        first + count - 1
+    );
+}
+
+//dates are given as YYYY-MM-DD (the start of that day) or YYYY-MM-DD HH:MM:SS
+fn get_date(value: Option<&str>) -> Result<Option<NaiveDateTime>> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let date = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        });
+
+    match date {
+        Some(date) => return Ok(Some(date)),
+        None => return Err(Error::InvalidDate(value.to_string())),
+    }
+}
+
+//keeps the files whose last modification falls in the range, files without a date never do
+fn filter_by_date(
+    found: Vec<FoundFile>,
+    date_range: &(Option<NaiveDateTime>, Option<NaiveDateTime>),
+    file_to_find: &str,
+) -> Result<Vec<FoundFile>> {
+    if date_range.0.is_none() && date_range.1.is_none() {
+        return Ok(found);
+    }
+
+    let mut filtered: Vec<FoundFile> = Vec::new();
+    for found in found {
+        let modified = match found.metadata.modified {
+            Some(modified) => modified,
+            None => continue,
+        };
+
+        let after_start = date_range.0.is_none_or(|start| modified >= start);
+        let before_end = date_range.1.is_none_or(|end| modified < end);
+        if after_start && before_end {
+            filtered.push(found);
+        }
+    }
+
+    if filtered.is_empty() {
+        return Err(Error::NotFound(file_to_find.to_string()));
+    }
+
+    return Ok(filtered);
+}
+
+//a regex only when asked for, otherwise a glob or a plain name depending on the characters used
+fn get_pattern(file_to_find: &str, regex_flag: bool) -> Result<Pattern> {
+    if regex_flag {
+        return Pattern::regex(file_to_find);
+    }
+
+    return Pattern::new(file_to_find);
+}
+
+//when a name matches several files, the user picks one of them (or all, if allowed)
+//the list and the prompt go to stderr, so they never end up in the output of /cat
+fn choose(
+    found: Vec<FoundFile>,
+    action: &str,
+    allow_all: bool,
+    all_flag: bool,
+) -> Result<Vec<FoundFile>> {
+    if found.len() == 1 || all_flag {
+        return Ok(found);
+    }
+
+    eprintln!("{} files match:", found.len());
+    for (i, found) in found.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, found.path);
+    }
+    if allow_all {
+        eprint!(
+            "Which one do you want to {}? (1-{} or all) ",
+            action,
+            found.len()
+        );
+    } else {
+        eprint!("Which one do you want to {}? (1-{}) ", action, found.len());
+    }
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+
+    if answer == "all" && allow_all {
+        return Ok(found);
+    }
+    match answer.parse::<usize>() {
+        Ok(i) if i >= 1 && i <= found.len() => return Ok(vec![found[i - 1].clone()]),
+        _ => return Err(Error::NothingSelected(action.to_string())),
+    }
 }
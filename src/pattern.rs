@@ -0,0 +1,128 @@
+use crate::error::*;
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+
+/// What `find` compares file names against.
+///
+/// Whether the comparison is case sensitive is up to the filesystem: FAT names are
+/// case insensitive, EXT2 names are not.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// A plain name, which must match the whole file name.
+    Name(String),
+    /// A shell style pattern such as `*.log` or `core.[0-9]*`.
+    Glob(glob::Pattern),
+    /// A regular expression, which matches if it is found anywhere in the name.
+    Regex {
+        case_sensitive: Regex,
+        case_insensitive: Regex,
+    },
+}
+
+impl Pattern {
+    /// A glob if `pattern` has any of `*`, `?` or `[` in it, a plain name otherwise.
+    pub fn new(pattern: &str) -> Result<Pattern> {
+        if !pattern.contains(['*', '?', '[']) {
+            return Ok(Pattern::Name(pattern.to_string()));
+        }
+
+        match glob::Pattern::new(pattern) {
+            Ok(glob) => return Ok(Pattern::Glob(glob)),
+            Err(e) => return Err(Error::InvalidPattern(format!("{}: {}", pattern, e))),
+        }
+    }
+
+    pub fn regex(pattern: &str) -> Result<Pattern> {
+        //both versions are built up front, the filesystem decides which one applies
+        let build = |case_insensitive: bool| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(case_insensitive)
+                .build()
+                .map_err(|e| Error::InvalidPattern(format!("{}: {}", pattern, e)))
+        };
+
+        return Ok(Pattern::Regex {
+            case_sensitive: build(false)?,
+            case_insensitive: build(true)?,
+        });
+    }
+
+    pub fn matches(&self, name: &str, case_sensitive: bool) -> bool {
+        match self {
+            Pattern::Name(pattern) if case_sensitive => return pattern == name,
+            Pattern::Name(pattern) => return pattern.eq_ignore_ascii_case(name),
+            Pattern::Glob(glob) => {
+                let options = glob::MatchOptions {
+                    case_sensitive,
+                    require_literal_separator: false,
+                    require_literal_leading_dot: false,
+                };
+                return glob.matches_with(name, options);
+            }
+            Pattern::Regex {
+                case_sensitive: regex,
+                ..
+            } if case_sensitive => return regex.is_match(name),
+            Pattern::Regex {
+                case_insensitive: regex,
+                ..
+            } => return regex.is_match(name),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Name(name) => write!(f, "{}", name),
+            Pattern::Glob(glob) => write!(f, "{}", glob.as_str()),
+            Pattern::Regex { case_sensitive, .. } => write!(f, "{}", case_sensitive.as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_match_the_whole_name() {
+        let pattern = Pattern::new("Readme.TXT").unwrap();
+        assert!(matches!(pattern, Pattern::Name(_)));
+
+        assert!(pattern.matches("Readme.TXT", true));
+        assert!(!pattern.matches("README.TXT", true));
+        assert!(pattern.matches("README.TXT", false));
+        assert!(!pattern.matches("Readme.TXT.bak", false));
+    }
+
+    #[test]
+    fn globs_follow_the_case_sensitivity() {
+        let pattern = Pattern::new("*.log").unwrap();
+        assert!(matches!(pattern, Pattern::Glob(_)));
+
+        assert!(pattern.matches("boot.log", true));
+        assert!(!pattern.matches("BOOT.LOG", true));
+        assert!(pattern.matches("BOOT.LOG", false));
+        assert!(!pattern.matches("boot.log.1", false));
+    }
+
+    #[test]
+    fn regexes_match_anywhere_in_the_name() {
+        let pattern = Pattern::regex("^core\\.[0-9]+").unwrap();
+
+        assert!(pattern.matches("core.1234", true));
+        assert!(!pattern.matches("CORE.1234", true));
+        assert!(pattern.matches("CORE.1234", false));
+        assert!(!pattern.matches("a.core.1234", false));
+
+        let pattern = Pattern::regex("tmp").unwrap();
+        assert!(pattern.matches("old_tmp_file", true));
+    }
+
+    #[test]
+    fn invalid_patterns_are_refused() {
+        assert!(matches!(Pattern::new("[a-"), Err(Error::InvalidPattern(_))));
+        assert!(matches!(Pattern::regex("("), Err(Error::InvalidPattern(_))));
+    }
+}
@@ -1,37 +1,107 @@
-pub mod utilities {
-    use byteorder::{ByteOrder, LittleEndian};
-    use chrono::*;
-    use std::convert::TryInto;
-    use std::io::{self, prelude::*, Seek, SeekFrom};
-
-    pub fn convert_to_utc_time(to_convert: [u8; 4]) -> chrono::DateTime<chrono::Utc> {
-        //convert unix time to current time
-        let timestamp = LittleEndian::read_u32(&to_convert);
-        let naive = NaiveDateTime::from_timestamp(timestamp.try_into().unwrap(), 0);
-
-        // Create a normal DateTime from the NaiveDateTime
-        let datetime: DateTime<Utc> = DateTime::from_utc(naive, Utc);
-        // Format the datetime how you want
-        return datetime;
-    }
+use byteorder::{ByteOrder, LittleEndian};
+use chrono::*;
+use std::convert::TryInto;
+use std::io::{self, prelude::*, Seek, SeekFrom};
+
+pub fn convert_to_utc_time(to_convert: [u8; 4]) -> chrono::DateTime<chrono::Utc> {
+    //convert unix time to current time
+    let timestamp = LittleEndian::read_u32(&to_convert);
+    let datetime: DateTime<Utc> = DateTime::from_timestamp(timestamp.into(), 0).unwrap_or_default();
+
+    // Format the datetime how you want
+    return datetime;
+}
+
+pub fn convert_dos_date(date: [u8; 2]) -> Option<NaiveDate> {
+    //DOS packs the date as year-1980/month/day in 7/4/5 bits, a zero date was never set
+    let date = LittleEndian::read_u16(&date) as u32;
+
+    return NaiveDate::from_ymd_opt(1980 + (date >> 9) as i32, (date >> 5) & 0xF, date & 0x1F);
+}
+
+pub fn convert_dos_date_time(
+    date: [u8; 2],
+    time: [u8; 2],
+    hundredths: u8,
+) -> Option<NaiveDateTime> {
+    //the time is hours/minutes/seconds in 5/6/5 bits, with the seconds counted in steps
+    //of 2, the creation time adds up to 199 hundredths of a second on top of that
+    let time = LittleEndian::read_u16(&time) as u32;
+    let hundredths = hundredths as u32;
+
+    return convert_dos_date(date)?.and_hms_milli_opt(
+        time >> 11,
+        (time >> 5) & 0x3F,
+        (time & 0x1F) * 2 + hundredths / 100,
+        (hundredths % 100) * 10,
+    );
+}
+
+pub fn seek_read(mut reader: impl Read + Seek, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(buf)?;
+    Ok(())
+}
+
+pub fn seek_write(mut reader: impl Write + Seek, offset: u128, buf: &[u8]) -> io::Result<()> {
+    let offset: u64 = offset
+        .try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "offset out of range"))?;
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.write_all(buf)?;
+    Ok(())
+}
 
-    pub fn seek_read(mut reader: impl Read + Seek, offset: u64, buf: &mut [u8]) -> io::Result<()> {
-        reader.seek(SeekFrom::Start(offset))?;
-        reader.read_exact(buf)?;
-        Ok(())
+pub fn split_path(path: &str) -> Vec<&str> {
+    //empty components come from repeated or trailing slashes, "." is the directory itself
+    return path
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect();
+}
+
+pub fn join_path(dir_path: &str, name: &str) -> String {
+    //the root is the only directory whose path already ends with a slash
+    if dir_path.ends_with('/') {
+        return format!("{}{}", dir_path, name);
     }
 
-    pub fn seek_write(
-        mut reader: impl Write + Seek,
-        offset: u128,
-        buf: &mut [u8],
-    ) -> io::Result<()> {
-        reader.seek(SeekFrom::Start(offset.try_into().unwrap()))?;
-        reader.write(buf)?;
-        Ok(())
+    return format!("{}/{}", dir_path, name);
+}
+
+pub fn remove_whitespace(s: &str) -> String {
+    s.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn dos_date_time_adds_the_hundredths_to_the_seconds() {
+        //2021-03-14 13:45:58, the time only has even seconds
+        let date = 0x526E_u16.to_le_bytes();
+        let time = 0x6DBD_u16.to_le_bytes();
+        let day = NaiveDate::from_ymd_opt(2021, 3, 14).unwrap();
+
+        assert_eq!(
+            convert_dos_date_time(date, time, 0),
+            day.and_hms_milli_opt(13, 45, 58, 0)
+        );
+        assert_eq!(
+            convert_dos_date_time(date, time, 150),
+            day.and_hms_milli_opt(13, 45, 59, 500)
+        );
+        assert_eq!(
+            convert_dos_date_time(date, time, 199),
+            day.and_hms_milli_opt(13, 45, 59, 990)
+        );
     }
 
-    pub fn remove_whitespace(s: &str) -> String {
-        s.chars().filter(|c| !c.is_whitespace()).collect()
+    #[test]
+    fn dos_date_time_of_an_unset_date_is_none() {
+        assert_eq!(convert_dos_date([0, 0]), None);
+        assert_eq!(convert_dos_date_time([0, 0], [0, 0], 0), None);
     }
 }